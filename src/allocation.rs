@@ -0,0 +1,149 @@
+// the search for the best split of a fixed number of places across the
+// buffers of Topology::buffers. every buffer keeps at least the places
+// one product needs from it, as a buffer with fewer would starve its
+// workstation, so only the places beyond those are distributed.
+
+pub type Allocation = Vec<usize>;
+
+choices! {
+    pub enum Search: "search" {
+        // evaluates every allocation
+        Exhaustive => "exhaustive",
+        // adds one place at a time where it helps most
+        Greedy => "greedy",
+    }
+}
+
+pub fn count(total: usize, minimum: &[usize]) -> usize {
+    // the number of allocations of total places, which is the binomial
+    // coefficient C(spare + n - 1, n - 1) of the stars and bars for the
+    // spare places over n buffers, or usize::MAX if it is any larger
+    let n = minimum.len();
+    match total.checked_sub(minimum.iter().sum()) {
+        None => 0,
+        Some(spare) => (1..n)
+            .try_fold(1usize, |acc, k| {
+                acc.checked_mul(spare + k).map(|product| product / k)
+            })
+            .unwrap_or(usize::MAX),
+    }
+}
+
+pub fn allocations(total: usize, minimum: &[usize]) -> Vec<Allocation> {
+    // every allocation of total places, i.e 15 for 1001 of them over five buffers
+    let mut all = vec![];
+    let mut current = minimum.to_vec();
+    let spare = total.saturating_sub(minimum.iter().sum());
+    fill(&mut all, &mut current, minimum, 0, spare);
+    all
+}
+
+fn fill(
+    all: &mut Vec<Allocation>,
+    current: &mut Allocation,
+    minimum: &[usize],
+    buffer: usize,
+    spare: usize,
+) {
+    // the last buffer takes whatever the others left
+    if buffer == current.len() - 1 {
+        current[buffer] = minimum[buffer] + spare;
+        all.push(current.clone());
+        return;
+    }
+    for extra in 0..=spare {
+        current[buffer] = minimum[buffer] + extra;
+        fill(all, current, minimum, buffer + 1, spare - extra);
+    }
+}
+
+pub fn greedy(
+    total: usize,
+    minimum: &[usize],
+    mut evaluate: impl FnMut(&Allocation) -> f64,
+) -> Allocation {
+    // marginal allocation: from the minimum in every buffer, gives each
+    // further place to the buffer where it raises the objective most.
+    // the evaluations should share their random numbers, so that the
+    // differences between the candidates of a step are the policy's
+    let mut current = minimum.to_vec();
+    for _ in minimum.iter().sum::<usize>()..total {
+        current = (0..current.len())
+            .map(|buffer| {
+                let mut candidate = current.clone();
+                candidate[buffer] += 1;
+                let value = evaluate(&candidate);
+                (candidate, value)
+            })
+            // the first of equal candidates, for a repeatable search
+            .reduce(|best, next| match next.1 > best.1 {
+                true => next,
+                false => best,
+            })
+            .unwrap()
+            .0;
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the five buffers of the default facility, each of which
+    // needs one place, so optimize --places 8 has 3 to spare
+    const MINIMUM: [usize; 5] = [1, 1, 1, 1, 1];
+
+    fn binomial(n: usize, k: usize) -> usize {
+        (0..k).map(|i| n - i).product::<usize>() / (1..=k).product::<usize>()
+    }
+
+    #[test]
+    fn count_is_the_stars_and_bars() {
+        // spare places over 5 buffers are C(spare + 4, 4)
+        for (places, expected) in [(5, 1), (7, 15), (8, 35), (10, 126)] {
+            assert_eq!(count(places, &MINIMUM), binomial(places - 5 + 4, 4));
+            assert_eq!(count(places, &MINIMUM), expected);
+        }
+        assert_eq!(count(4, &MINIMUM), 0);
+        assert_eq!(count(1000, &[0; 40]), usize::MAX);
+    }
+
+    #[test]
+    fn allocations_are_every_split_once() {
+        let minimum = [1, 2, 1];
+        let all = allocations(7, &minimum);
+        assert_eq!(all.len(), count(7, &minimum));
+        assert_eq!(all.first().unwrap(), &[1, 2, 4]);
+        assert_eq!(all.last().unwrap(), &[4, 2, 1]);
+        for (i, allocation) in all.iter().enumerate() {
+            assert_eq!(allocation.iter().sum::<usize>(), 7);
+            assert!(allocation.iter().zip(minimum).all(|(a, m)| *a >= m));
+            assert!(!all[..i].contains(allocation));
+        }
+        assert_eq!(allocations(8, &MINIMUM).len(), 35);
+    }
+
+    #[test]
+    fn greedy_climbs_to_the_best_allocation() {
+        // a concave objective whose best allocation of 9 is 1, 5, 3
+        let target = [1.0, 5.0, 3.0];
+        let mut evaluations = 0;
+        let best = greedy(9, &[1, 1, 1], |a| {
+            evaluations += 1;
+            -a.iter()
+                .zip(target)
+                .map(|(a, t)| (*a as f64 - t).powi(2))
+                .sum::<f64>()
+        });
+        assert_eq!(best, [1, 5, 3]);
+        // one candidate per buffer for each of the 6 spare places
+        assert_eq!(evaluations, 18);
+    }
+
+    #[test]
+    fn greedy_breaks_ties_with_the_first_buffer() {
+        assert_eq!(greedy(5, &[1, 1, 1], |_| 0.0), [3, 1, 1]);
+        assert_eq!(greedy(3, &[1, 1, 1], |_| unreachable!()), [1, 1, 1]);
+    }
+}
@@ -0,0 +1,163 @@
+// batch means: one long run is cut into consecutive batches, and
+// the batch means are treated as independent observations. they
+// only are if the batches are long compared with the correlation
+// of the output, which the lag-1 autocorrelation of the means checks.
+
+choices! {
+    pub enum Batching: "batching" {
+        // batches of equal simulated time
+        Time => "time",
+        // batches holding an equal number of product completions
+        Products => "products",
+    }
+}
+
+pub fn bounds(
+    batching: Batching,
+    start: f64,
+    end: f64,
+    completions: &[f64],
+    k: usize,
+) -> std::result::Result<Vec<f64>, String> {
+    // the k + 1 times between which k batches lie after start. completions
+    // are the sorted times products were finished, for batches by count
+    if start >= end {
+        return Err(format!(
+            "the warm-up of {start} minutes is not before the end of the run at {end:.0}"
+        ));
+    }
+    match batching {
+        Batching::Time => Ok((0..=k)
+            .map(|j| start + (end - start) * j as f64 / k as f64)
+            .collect()),
+        Batching::Products => {
+            let after = completions
+                .iter()
+                .filter(|t| **t > start && **t <= end)
+                .copied()
+                .collect::<Vec<f64>>();
+            if after.len() < k {
+                return Err(format!(
+                    "only {} products were finished after the warm-up, \
+                    too few for {k} batches",
+                    after.len()
+                ));
+            }
+            let size = after.len() / k;
+            // each batch ends at the completion of its last product
+            let mut bounds = vec![start];
+            bounds.extend((1..=k).map(|j| after[j * size - 1]));
+            Ok(bounds)
+        }
+    }
+}
+
+pub fn step_averages(steps: &[(f64, f64)], bounds: &[f64]) -> Vec<f64> {
+    // the time average between each pair of bounds of a step function
+    // which takes each value from its time until the time of the next
+    let last = bounds[bounds.len() - 1];
+    let mut area = vec![0.0; bounds.len() - 1];
+    for (i, (start, value)) in steps.iter().enumerate() {
+        let end = steps.get(i + 1).map_or(last, |(t, _)| *t).min(last);
+        // the first batch ending after the start of the step
+        let mut batch = bounds[1..].partition_point(|b| *b <= *start);
+        let mut t = start.max(bounds[0]);
+        while t < end && batch < area.len() {
+            let batch_end = bounds[batch + 1].min(end);
+            area[batch] += value * (batch_end - t);
+            t = batch_end;
+            batch += 1;
+        }
+    }
+    area.iter()
+        .zip(bounds.windows(2))
+        .map(|(a, b)| a / (b[1] - b[0]))
+        .collect()
+}
+
+pub fn rates(times: &[f64], bounds: &[f64]) -> Vec<f64> {
+    // the number of times per minute between each pair of bounds
+    bounds
+        .windows(2)
+        .map(|b| times.iter().filter(|t| **t > b[0] && **t <= b[1]).count() as f64 / (b[1] - b[0]))
+        .collect()
+}
+
+pub fn lag1_autocorrelation(x: &[f64]) -> f64 {
+    let n = x.len();
+    let mean = x.iter().sum::<f64>() / n as f64;
+    let variance = x.iter().map(|xi| (xi - mean).powi(2)).sum::<f64>();
+    let covariance = x
+        .windows(2)
+        .map(|w| (w[0] - mean) * (w[1] - mean))
+        .sum::<f64>();
+    match variance > 0.0 {
+        true => covariance / variance,
+        // a constant measure has no correlation to worry about
+        false => 0.0,
+    }
+}
+
+pub fn acceptable(r1: f64, k: usize) -> bool {
+    // with independent means r1 is about normal with a standard
+    // deviation of 1 / sqrt(k), and positive correlation is what
+    // makes the interval too narrow, so a one sided 5% test
+    r1 < 1.645 / (k as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_batches_split_the_run_evenly() {
+        let bounds = bounds(Batching::Time, 100.0, 500.0, &[], 4).unwrap();
+        assert_eq!(bounds, [100.0, 200.0, 300.0, 400.0, 500.0]);
+    }
+
+    #[test]
+    fn product_batches_end_at_a_completion() {
+        // 7 completions after the warm-up make 3 batches of 2, and
+        // the last is left over
+        let completions = [50.0, 110.0, 120.0, 130.0, 140.0, 150.0, 160.0, 170.0, 600.0];
+        let bounds = bounds(Batching::Products, 100.0, 500.0, &completions, 3).unwrap();
+        assert_eq!(bounds, [100.0, 120.0, 140.0, 160.0]);
+    }
+
+    #[test]
+    fn rejects_a_warm_up_past_the_end_and_too_few_products() {
+        assert_eq!(
+            bounds(Batching::Time, 500.0, 500.0, &[], 4).unwrap_err(),
+            "the warm-up of 500 minutes is not before the end of the run at 500"
+        );
+        assert_eq!(
+            bounds(Batching::Products, 100.0, 500.0, &[50.0, 150.0, 250.0], 3).unwrap_err(),
+            "only 2 products were finished after the warm-up, too few for 3 batches"
+        );
+    }
+
+    #[test]
+    fn step_averages_weigh_each_value_by_its_time() {
+        // 2 until 15, 4 until 25, then 0
+        let steps = [(0.0, 2.0), (15.0, 4.0), (25.0, 0.0)];
+        assert_eq!(step_averages(&steps, &[10.0, 20.0, 30.0]), [3.0, 2.0]);
+        assert_eq!(step_averages(&steps, &[0.0, 40.0]), [1.75]);
+    }
+
+    #[test]
+    fn rates_count_times_per_minute() {
+        assert_eq!(
+            rates(&[1.0, 2.0, 3.0, 12.0], &[0.0, 10.0, 20.0]),
+            [0.3, 0.1]
+        );
+    }
+
+    #[test]
+    fn lag1_autocorrelation_of_known_series() {
+        assert_eq!(lag1_autocorrelation(&[1.0, -1.0, 1.0, -1.0]), -0.75);
+        assert_eq!(lag1_autocorrelation(&[2.0; 5]), 0.0);
+        // the one sided 5% bound for 100 batches is 0.1645
+        assert!(acceptable(0.16, 100) && !acceptable(0.17, 100));
+        assert!(acceptable(-0.75, 4));
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Formatter, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::component::Component;
+use crate::product::Product;
+use crate::simulation::{Duration, TimeStamp};
+use crate::topology::{Kind, Topology};
+use crate::workstation::{Buffer, Type};
+
+// snapshots of a paused simulation in a plain text format. every
+// line is a label followed by whitespace separated values, with floats
+// written in full so that a restored run continues exactly as if it
+// had never stopped. lines starting with # are comments
+
+pub trait Checkpoint {
+    fn save(&self, out: &mut Writer);
+    // restores in place, since the actors hold references
+    // to each other which cannot be written to the file
+    fn restore(&mut self, input: &mut Reader) -> std::result::Result<(), CheckpointError>;
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(PathBuf, io::Error),
+    // the line, and what was wrong with it
+    Format(usize, String),
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Io(path, e) => write!(f, "could not access {}: {}", path.display(), e),
+            Self::Format(line, message) => write!(f, "checkpoint line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+#[derive(Default)]
+pub struct Writer {
+    text: String,
+}
+
+impl Writer {
+    pub fn write(&self, path: &Path) -> std::result::Result<(), CheckpointError> {
+        fs::write(path, format!("{}\n", self.text))
+            .map_err(|e| CheckpointError::Io(path.to_path_buf(), e))
+    }
+
+    // starts a new line
+    pub fn line(&mut self, label: &str) -> &mut Self {
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+        self.text.push_str(label);
+        self
+    }
+
+    pub fn value(&mut self, value: impl Debug) -> &mut Self {
+        // debug formatting round trips floats exactly
+        self.text.push_str(&format!(" {value:?}"));
+        self
+    }
+
+    pub fn word(&mut self, word: &str) -> &mut Self {
+        self.text.push(' ');
+        self.text.push_str(word);
+        self
+    }
+
+    pub fn time(&mut self, ts: Option<TimeStamp>) -> &mut Self {
+        match ts {
+            Some(ts) => self.value(ts.get()),
+            None => self.word("-"),
+        }
+    }
+
+    pub fn times<'a>(&mut self, times: impl ExactSizeIterator<Item = &'a TimeStamp>) -> &mut Self {
+        self.value(times.len());
+        for ts in times {
+            self.value(ts.get());
+        }
+        self
+    }
+
+    pub fn durations(&mut self, durations: &VecDeque<Duration>) -> &mut Self {
+        self.value(durations.len());
+        for d in durations {
+            self.value(d.as_minutes());
+        }
+        self
+    }
+
+    pub fn component(&mut self, c: Option<&Component>) -> &mut Self {
+        // the kind, inspection duration, inspection
+        // start and end and the time it was enqueued
+        match c {
+            None => self.word("-"),
+            Some(c) => self
+                .word(c.name())
+                .value(c.duration.as_minutes())
+                .time(c.start)
+                .time(c.end)
+                .time(c.queue_time),
+        }
+    }
+
+    pub fn product(&mut self, p: &Product) -> &mut Self {
+        // the kind, time and number of components, then each component
+        self.word(p.name())
+            .time(Some(p.timestamp()))
+            .value(p.component_count());
+        for c in p.components.iter() {
+            self.component(Some(c));
+        }
+        self
+    }
+
+    pub fn ws_type(&mut self, ws_type: &Type) -> &mut Self {
+        // the name, then the component, capacity, length
+        // and components in order of arrival of each buffer
+        self.word(ws_type.name());
+        for buf in ws_type.buffers() {
+            self.word(&buf.kind().name)
+                .value(buf.capacity())
+                .value(buf.len());
+            for c in buf.components() {
+                self.component(Some(c));
+            }
+        }
+        self
+    }
+}
+
+pub struct Reader {
+    // every value in the file with the line it is on
+    tokens: Vec<(usize, String)>,
+    next: usize,
+    // the facility the checkpoint is of, which names its elements
+    topology: Rc<Topology>,
+}
+
+type ReadResult<T> = std::result::Result<T, CheckpointError>;
+
+impl Reader {
+    pub fn read(path: &Path, topology: Rc<Topology>) -> ReadResult<Self> {
+        let text =
+            fs::read_to_string(path).map_err(|e| CheckpointError::Io(path.to_path_buf(), e))?;
+        let tokens = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim_start().starts_with('#'))
+            .flat_map(|(i, line)| line.split_whitespace().map(move |t| (i + 1, t.to_string())))
+            .collect();
+        Ok(Reader {
+            tokens,
+            next: 0,
+            topology,
+        })
+    }
+
+    pub fn error(&self, message: String) -> CheckpointError {
+        // the line of the last value read, or the last line if it ran out
+        let line = match self.tokens.get(self.next.saturating_sub(1)) {
+            Some((line, _)) => *line,
+            None => self.tokens.last().map_or(0, |(line, _)| *line),
+        };
+        CheckpointError::Format(line, message)
+    }
+
+    pub fn word(&mut self) -> ReadResult<String> {
+        match self.tokens.get(self.next) {
+            Some((_, token)) => {
+                self.next += 1;
+                Ok(token.clone())
+            }
+            None => {
+                self.next += 1;
+                Err(self.error("unexpected end of the checkpoint".to_string()))
+            }
+        }
+    }
+
+    pub fn expect(&mut self, label: &str) -> ReadResult<()> {
+        let word = self.word()?;
+        match word == label {
+            true => Ok(()),
+            false => Err(self.error(format!("expected {label:?}, found {word:?}"))),
+        }
+    }
+
+    pub fn value<T: FromStr>(&mut self) -> ReadResult<T>
+    where
+        T::Err: Display,
+    {
+        let word = self.word()?;
+        word.parse::<T>()
+            .map_err(|e| self.error(format!("could not parse {word:?}: {e}")))
+    }
+
+    pub fn finish(&mut self) -> ReadResult<()> {
+        match self.next < self.tokens.len() {
+            true => {
+                self.next += 1;
+                Err(self.error("unexpected data after the checkpoint".to_string()))
+            }
+            false => Ok(()),
+        }
+    }
+
+    fn is_none(&mut self) -> bool {
+        // consumes a "-" if it is next
+        let none = matches!(self.tokens.get(self.next), Some((_, t)) if t == "-");
+        if none {
+            self.next += 1;
+        }
+        none
+    }
+
+    pub fn time(&mut self) -> ReadResult<Option<TimeStamp>> {
+        match self.is_none() {
+            true => Ok(None),
+            false => Ok(Some(
+                TimeStamp::start() + Duration::of_minutes(self.value::<f64>()?),
+            )),
+        }
+    }
+
+    pub fn times(&mut self) -> ReadResult<Vec<TimeStamp>> {
+        let count = self.value::<usize>()?;
+        (0..count)
+            .map(|_| {
+                self.time()?
+                    .ok_or_else(|| self.error("a time cannot be missing here".to_string()))
+            })
+            .collect()
+    }
+
+    pub fn durations(&mut self) -> ReadResult<VecDeque<Duration>> {
+        let count = self.value::<usize>()?;
+        (0..count)
+            .map(|_| Ok(Duration::of_minutes(self.value::<f64>()?)))
+            .collect()
+    }
+
+    fn kind(&mut self, label: &str, names: Vec<&str>) -> ReadResult<Kind> {
+        let name = self.word()?;
+        match names.iter().position(|n| *n == name) {
+            Some(index) => Ok(Kind {
+                index,
+                name: Rc::from(names[index]),
+            }),
+            None => Err(self.error(format!("no such {label} {name:?}"))),
+        }
+    }
+
+    pub fn component(&mut self) -> ReadResult<Option<Component>> {
+        if self.is_none() {
+            return Ok(None);
+        }
+        let topology = self.topology.clone();
+        let names = topology.components.iter().map(|c| c.name.as_str());
+        let kind = self.kind("component", names.collect())?;
+        Ok(Some(Component {
+            kind,
+            duration: Duration::of_minutes(self.value::<f64>()?),
+            start: self.time()?,
+            end: self.time()?,
+            queue_time: self.time()?,
+        }))
+    }
+
+    fn present_component(&mut self) -> ReadResult<Component> {
+        self.component()?
+            .ok_or_else(|| self.error("a product cannot be missing a component".to_string()))
+    }
+
+    pub fn product(&mut self) -> ReadResult<Product> {
+        let topology = self.topology.clone();
+        let names = topology.products.iter().map(|p| p.name.as_str());
+        let kind = self.kind("product", names.collect())?;
+        let ts = self
+            .time()?
+            .ok_or_else(|| self.error("a product needs a time".to_string()))?;
+        let components = (0..self.value::<usize>()?)
+            .map(|_| self.present_component())
+            .collect::<ReadResult<Vec<Component>>>()?;
+        let mut recipe = components.iter().map(|c| c.kind.index).collect::<Vec<_>>();
+        let mut expected = topology.products[kind.index].recipe.clone();
+        recipe.sort();
+        expected.sort();
+        match recipe == expected && components.iter().all(|c| c.is_finished()) {
+            true => Ok(Product::new(kind, components, ts)),
+            false => Err(self.error(format!("{kind} is not made of these components"))),
+        }
+    }
+
+    pub fn ws_type(&mut self) -> ReadResult<Type> {
+        let topology = self.topology.clone();
+        let names = topology.workstations.iter().map(|ws| ws.name.as_str());
+        let kind = self.kind("workstation", names.collect())?;
+        let mut buffers = vec![];
+        for def in topology
+            .buffers()
+            .iter()
+            .filter(|b| b.workstation == kind.index)
+        {
+            self.expect(&topology.components[def.component].name)?;
+            let component = topology.component(def.component);
+            let mut buffer = Buffer::new(component.clone(), def.needed, self.value()?);
+            for _ in 0..self.value::<usize>()? {
+                let c = self.present_component()?;
+                if c.kind != component {
+                    return Err(
+                        self.error(format!("a {c} cannot wait in the buffer of {component}"))
+                    );
+                }
+                if !buffer.push(c) {
+                    return Err(self.error(format!(
+                        "a buffer of capacity {} cannot hold more components",
+                        buffer.capacity()
+                    )));
+                }
+            }
+            buffers.push(buffer);
+        }
+        Ok(Type::new(kind, buffers))
+    }
+}
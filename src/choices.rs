@@ -0,0 +1,77 @@
+// an enum of settings picked by name, on the command line or in a
+// scenario. it gets ALL, in the order given, name(), a Display which
+// pads the name and a FromStr which matches it whatever the case,
+// listing every name when given one it does not know, i.e
+//   choices! {
+//       pub enum Format: "format" {
+//           Text => "text",
+//           Csv => "csv",
+//       }
+//   }
+macro_rules! choices {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident: $what:literal {
+            $($(#[$variant_meta:meta])* $variant:ident => $text:literal,)+
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)+
+        }
+
+        impl $name {
+            pub const ALL: [$name; [$(stringify!($variant)),+].len()] = [$($name::$variant),+];
+
+            pub fn name(&self) -> &str {
+                match self {
+                    $(Self::$variant => $text,)+
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.pad(self.name())
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+                Self::ALL
+                    .iter()
+                    .find(|c| c.name() == s.trim().to_lowercase())
+                    .copied()
+                    .ok_or(format!(
+                        "unknown {} {s:?}, expected one of {}",
+                        $what,
+                        Self::ALL.map(|c| c.name().to_string()).join(", ")
+                    ))
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    choices! {
+        pub enum Shape: "shape" {
+            Square => "square",
+            Star => "star**",
+        }
+    }
+
+    #[test]
+    fn parses_and_shows_the_names() {
+        assert_eq!(Shape::ALL, [Shape::Square, Shape::Star]);
+        assert_eq!(" STAR** ".parse::<Shape>(), Ok(Shape::Star));
+        assert_eq!(format!("[{:>7}]", Shape::Square), "[ square]");
+        assert_eq!(
+            "circle".parse::<Shape>(),
+            Err("unknown shape \"circle\", expected one of square, star**".to_string())
+        );
+    }
+}
@@ -0,0 +1,1692 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::allocation::{self, Allocation, Search};
+use crate::batch::{self, Batching};
+use crate::checkpoint::{Checkpoint, Reader, Writer};
+use crate::inspector::Routing;
+use crate::random::{Generator, Random};
+use crate::report::Format;
+use crate::results::{Measure, ReplicationResult, ResultsAggregator};
+use crate::scenario::Scenario;
+use crate::simulation::{Duration, TimeStamp};
+use crate::statistics::ConfidenceInterval;
+use crate::topology::Topology;
+use crate::trace::Traces;
+use crate::{
+    auto_warmup, batch_series, bracketed, detect_warmup, end_time, facility_stats, resolve_warmup,
+    run_iteration, Facility, InputModel, RunConfig, ServiceTimes,
+};
+use crate::{control, fitting, gof, plots, random, report, rngtest, statistics, trace};
+use crate::{COMPONENT_COUNT, INIT_R, MAX_R, SERIES, WARMUP_INTERVAL, WARMUP_WINDOW};
+
+// the default length of the single run batch means uses
+const BATCH_COMPONENTS: usize = 50_000;
+// the most allocations an automatic search evaluates one by one
+const EXHAUSTIVE_ALLOCATIONS: usize = 500;
+
+const USAGE: &str = "\
+usage: rust-simulation [COMMAND] [OPTIONS]
+
+commands:
+  run                  replicate until every measure meets its precision (the default)
+  fit [DIR]            fit distributions to the observed service times in DIR
+  compare [FILE...]    pair two or more scenario files, or the routing policies
+  sweep                run every point of a grid given by --vary SETTING=VALUE|VALUE..
+  trace                print the event log of one replication
+  rngtest              test the random number generators
+  replay [DIR]         run one replication on the observed service times
+  warmup               suggest a warm-up from Welch's method and MSER-5
+  batch                estimate by batch means over one long run
+  antithetic           compare independent and antithetic replications
+  optimize             split --places buffer places for the most throughput
+  checkpoint FILE      save a replication at --at minutes
+  resume FILE          finish a saved replication
+
+options:
+  --scenario FILE      the facility, distributions and run settings
+  --facility FILE      a facility, with the default settings
+  --seed N             the seed of the random number streams
+  --replications N     the replications to run, fixing the count for run
+  --format text|csv    how run, fit, compare, sweep, trace and rngtest print
+  --rng, --routing, --capacities, --warmup, --precision and --STREAM
+                       override the scenario, i.e --ws2 \"gamma(2, 3)\"
+";
+
+// the arguments of the command line, marked as a command uses them so
+// that any left over, such as a misspelt option, stops the program
+// instead of being silently ignored
+struct Args {
+    args: Vec<String>,
+    used: Vec<bool>,
+    // where the arguments after the command which are not options start
+    next: usize,
+}
+
+impl Args {
+    fn new(args: Vec<String>) -> Self {
+        // the first is the program itself
+        let mut used = vec![false; args.len()];
+        if let Some(program) = used.first_mut() {
+            *program = true;
+        }
+        Args {
+            args,
+            used,
+            next: 1,
+        }
+    }
+
+    // the command, or run when the options come first
+    fn command(&mut self) -> String {
+        match self.args.get(1) {
+            Some(command) if !command.starts_with("--") || command == "--help" => {
+                self.used[1] = true;
+                self.next = 2;
+                command.clone()
+            }
+            _ => "run".to_string(),
+        }
+    }
+
+    // the next argument after the command which is not an option,
+    // i.e the directory of fit or the files of compare
+    fn positional(&mut self) -> Option<String> {
+        match self.args.get(self.next) {
+            Some(arg) if !arg.starts_with("--") => {
+                self.used[self.next] = true;
+                self.next += 1;
+                Some(arg.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        let mut found = false;
+        for (arg, used) in self.args.iter().zip(self.used.iter_mut()) {
+            if arg == name {
+                *used = true;
+                found = true;
+            }
+        }
+        found
+    }
+
+    // every value of an option which may be repeated, i.e --vary
+    fn values(&mut self, name: &str) -> Vec<String> {
+        let mut values = vec![];
+        for i in 0..self.args.len() {
+            if self.args[i] == name {
+                self.used[i] = true;
+                match self.args.get(i + 1) {
+                    Some(value) if !value.starts_with("--") => {
+                        self.used[i + 1] = true;
+                        values.push(value.clone());
+                    }
+                    _ => fail(format!("{name} needs a value")),
+                }
+            }
+        }
+        values
+    }
+
+    // the value of an option, or None when it is not given
+    fn value(&mut self, name: &str) -> Option<String> {
+        let mut values = self.values(name);
+        match values.len() {
+            0 | 1 => values.pop(),
+            _ => fail(format!("{name} is given more than once")),
+        }
+    }
+
+    // the value of an option parsed, which stops the program with
+    // "{name} must be {expected}" unless it parses and passes the check
+    fn parse<T: FromStr>(
+        &mut self,
+        name: &str,
+        check: impl Fn(&T) -> bool,
+        expected: &str,
+    ) -> Option<T> {
+        self.value(name)
+            .map(|value| match value.trim().parse::<T>() {
+                Ok(value) if check(&value) => value,
+                _ => fail(format!("{name} must be {expected}")),
+            })
+    }
+
+    // the value of an option naming one of a family, i.e --by products,
+    // which stops the program with the family's own error
+    fn choice<T: FromStr<Err = String>>(&mut self, name: &str) -> Option<T> {
+        self.value(name).map(|value| match value.parse::<T>() {
+            Ok(value) => value,
+            Err(e) => fail(e),
+        })
+    }
+
+    // stops the program at the first argument no command used
+    fn finish(&self) {
+        if let Some(i) = self.used.iter().position(|used| !used) {
+            let arg = &self.args[i];
+            match arg.starts_with("--") {
+                true => fail(format!("unknown option: {arg}\n\n{USAGE}")),
+                false => fail(format!("unexpected argument: {arg}\n\n{USAGE}")),
+            }
+        }
+    }
+}
+
+// the one way the command line stops on an error
+fn fail(message: impl Display) -> ! {
+    eprintln!("error: {message}");
+    std::process::exit(1);
+}
+
+// the measures of a replication, which stops the program
+// when the warm-up leaves nothing of the run to measure
+fn measured(result: std::result::Result<ReplicationResult, String>) -> ReplicationResult {
+    result.unwrap_or_else(|e| fail(e))
+}
+
+// options override the scenario, i.e --routing original or --ws2 gamma
+fn configure(args: &mut Args, scenario: &mut Scenario) {
+    for name in scenario.options() {
+        if let Some(value) = args.value(&format!("--{name}")) {
+            if let Err(e) = scenario.set_option(&name, &value) {
+                fail(format!("--{name}: {e}"));
+            }
+        }
+    }
+}
+
+// the number of replications, i.e --replications 20
+fn replications(args: &mut Args, least: usize, generator: Generator) -> Option<usize> {
+    let most = most_replications(generator);
+    let expected = format!("between {least} and {most}");
+    args.parse("--replications", |r| (least..=most).contains(r), &expected)
+}
+
+// the replication to run, i.e --replication 3
+fn replication(args: &mut Args, generator: Generator) -> u64 {
+    let most = most_replications(generator);
+    let expected = format!("below {most}");
+    args.parse("--replication", |r| *r < most as u64, &expected)
+        .unwrap_or(0)
+}
+
+// each replication draws from its own substream of every stream
+fn most_replications(generator: Generator) -> usize {
+    (MAX_R as u64).min(generator.substreams()) as usize
+}
+
+// the directory of observed service times, i.e fit data
+fn data_dir(args: &mut Args) -> PathBuf {
+    PathBuf::from(args.positional().unwrap_or(trace::DATA_DIR.to_string()))
+}
+
+// the directory to write plots to, if any. --svg on
+// its own writes to the default plot directory
+fn plot_dir(args: &mut Args) -> (Option<PathBuf>, bool) {
+    let svg = args.flag("--svg");
+    let plot_dir = match args.value("--plots") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None if svg => Some(PathBuf::from("plots")),
+        None => None,
+    };
+    (plot_dir, svg)
+}
+
+pub fn run(args: Vec<String>) {
+    let mut args = Args::new(args);
+    let command = args.command();
+    if matches!(command.as_str(), "help" | "--help" | "-h") {
+        print!("{USAGE}");
+        return;
+    }
+    let format = args.choice::<Format>("--format").unwrap_or(Format::Text);
+    if format != Format::Text
+        && !["run", "fit", "compare", "sweep", "trace", "rngtest"].contains(&command.as_str())
+    {
+        fail(format!(
+            "--format {format} is only for run, fit, compare, sweep, trace and rngtest"
+        ));
+    }
+    // the facility and settings simulated, the built-in ones unless
+    // --scenario or --facility is given
+    let mut scenario = match (args.value("--scenario"), args.value("--facility")) {
+        (None, None) => Scenario::new(Rc::default()),
+        (Some(path), None) => match Scenario::load(Path::new(&path)) {
+            Ok(scenario) => scenario,
+            Err(e) => fail(e),
+        },
+        (None, Some(path)) => match Topology::load(Path::new(&path)) {
+            Ok(topology) => Scenario::new(Rc::new(topology)),
+            Err(e) => fail(e),
+        },
+        (Some(_), Some(_)) => {
+            fail("give the facility in the [facility] section of the scenario, not --facility")
+        }
+    };
+    configure(&mut args, &mut scenario);
+    let topology = scenario.topology.clone();
+    match command.as_str() {
+        "replay" => {
+            let dir = data_dir(&mut args);
+            args.finish();
+            replay(&scenario, &dir);
+        }
+        "checkpoint" => {
+            // i.e checkpoint state.txt --at 5000 --replication 3
+            let Some(path) = args.positional() else {
+                fail("checkpoint needs a file to write to");
+            };
+            let at = args.parse("--at", |at| *at >= 0.0, "a time in minutes");
+            let Some(at) = at else {
+                fail("checkpoint needs --at, the time in minutes to stop at");
+            };
+            let replication = replication(&mut args, scenario.generator);
+            args.finish();
+            checkpoint(
+                Path::new(&path),
+                at,
+                replication,
+                &scenario,
+                &load_model(&scenario),
+            );
+        }
+        "resume" => {
+            let Some(path) = args.positional() else {
+                fail("resume needs a checkpoint file");
+            };
+            let Some(warmup) = scenario.warmup else {
+                fail("resume cannot detect a warm-up, give it in minutes");
+            };
+            args.finish();
+            resume(topology, Path::new(&path), warmup);
+        }
+        "rngtest" => {
+            let samples = args
+                .parse("--samples", |s| *s >= 1000, "at least 1000")
+                .unwrap_or(100_000);
+            // i.e --lags 1,2,5
+            let lags = match args.value("--lags").map(|l| {
+                l.split(',')
+                    .map(|lag| lag.trim().parse::<usize>())
+                    .collect::<std::result::Result<Vec<usize>, _>>()
+            }) {
+                None => vec![1, 2, 5, 10],
+                Some(Ok(lags)) if lags.iter().all(|lag| *lag > 0 && *lag < samples) => lags,
+                Some(_) => fail("--lags must be a list of lags such as 1,2,5"),
+            };
+            let alpha = args
+                .parse(
+                    "--alpha",
+                    |a| *a > 0.0 && *a < 1.0,
+                    "a significance level between 0 and 1",
+                )
+                .unwrap_or(0.01);
+            // the substreams of the first replications
+            let substreams = replications(&mut args, 1, scenario.generator).unwrap_or(1);
+            args.finish();
+            let settings = (samples, lags.as_slice(), alpha);
+            rngtest(
+                scenario.generator,
+                scenario.seed,
+                substreams,
+                settings,
+                format,
+            );
+        }
+        "compare" => {
+            // i.e compare a.toml b.toml c.toml, each against the first,
+            // or the routing policies of one scenario without any
+            let files = std::iter::from_fn(|| args.positional()).collect::<Vec<String>>();
+            let (what, scenarios) = match files.len() {
+                0 => (
+                    "Routing policies",
+                    Routing::ALL
+                        .map(|routing| {
+                            let scenario = Scenario {
+                                routing,
+                                ..scenario.clone()
+                            };
+                            (routing.name().to_string(), scenario)
+                        })
+                        .to_vec(),
+                ),
+                1 => fail("compare needs two or more scenarios, or none to compare routings"),
+                _ => {
+                    let scenarios = files
+                        .iter()
+                        .map(|file| {
+                            let path = Path::new(file);
+                            let mut other = match Scenario::load(path) {
+                                Ok(other) => other,
+                                Err(e) => fail(e),
+                            };
+                            configure(&mut args, &mut other);
+                            let name = path.file_stem().unwrap_or(path.as_os_str());
+                            (name.to_string_lossy().to_string(), other)
+                        })
+                        .collect::<Vec<(String, Scenario)>>();
+                    // the measures of one facility are paired with another's
+                    // by name, and the streams by the first scenario's seed
+                    let (_, first) = &scenarios[0];
+                    let names = |s: &Scenario| {
+                        Measure::all(&s.topology)
+                            .iter()
+                            .map(|measure| measure.name(&s.topology))
+                            .collect::<Vec<String>>()
+                    };
+                    for (name, other) in scenarios.iter().skip(1) {
+                        if names(other) != names(first) {
+                            fail(format!(
+                                "{name} has other measures than {}, compare needs \
+                                scenarios of the same facility",
+                                scenarios[0].0
+                            ));
+                        }
+                    }
+                    let seed = first.seed;
+                    let scenarios = scenarios
+                        .into_iter()
+                        .map(|(name, other)| (name, Scenario { seed, ..other }))
+                        .collect();
+                    ("Scenarios", scenarios)
+                }
+            };
+            let replications = replications(&mut args, 2, scenario.generator).unwrap_or(20);
+            args.finish();
+            compare(what, &scenarios, replications, format);
+        }
+        "sweep" => {
+            // i.e sweep --vary capacities=1|2|3 --vary routing=new|original,
+            // every combination of the values with the last varying fastest
+            let mut names = vec![];
+            let mut points = vec![(vec![], scenario.clone())];
+            for axis in args.values("--vary") {
+                let Some((name, values)) = axis.split_once('=') else {
+                    fail("--vary must be a setting and its values, i.e routing=new|original");
+                };
+                let name = name.trim();
+                if !scenario.options().iter().any(|option| option == name) {
+                    fail(format!(
+                        "--vary: no setting {name:?}, expected one of {}",
+                        scenario.options().join(", ")
+                    ));
+                }
+                let mut grid = vec![];
+                for (values_so_far, point) in points {
+                    for value in values.split('|') {
+                        let mut point = point.clone();
+                        if let Err(e) = point.set_option(name, value) {
+                            fail(format!("--vary {name}: {e}"));
+                        }
+                        let mut values = values_so_far.clone();
+                        values.push(value.trim().to_string());
+                        grid.push((values, point));
+                    }
+                }
+                names.push(name.to_string());
+                points = grid;
+            }
+            if names.is_empty() {
+                fail("sweep needs a --vary setting=value|value.. for each axis of the grid");
+            }
+            let replications = replications(&mut args, 2, scenario.generator).unwrap_or(INIT_R);
+            args.finish();
+            sweep(&names, &points, replications, format);
+        }
+        "trace" => {
+            // i.e trace --replication 3 --until 500
+            let replication = replication(&mut args, scenario.generator);
+            let until = args.parse("--until", |u| *u >= 0.0, "a number of minutes");
+            args.finish();
+            trace_replication(
+                &scenario,
+                &load_model(&scenario),
+                replication,
+                until,
+                format,
+            );
+        }
+        "antithetic" => {
+            let expected = format!("between 2 and {}", MAX_R / 2);
+            let pairs = args
+                .parse("--pairs", |p| (2..=MAX_R / 2).contains(p), &expected)
+                .unwrap_or(10);
+            args.finish();
+            antithetic(&scenario, &load_model(&scenario), pairs);
+        }
+        "warmup" => {
+            let replications = replications(&mut args, 1, scenario.generator).unwrap_or(INIT_R);
+            let interval = args
+                .parse("--interval", |i| *i > 0.0, "a positive number of minutes")
+                .unwrap_or(WARMUP_INTERVAL);
+            let window = args
+                .parse("--window", |_| true, "a whole number of intervals")
+                .unwrap_or(WARMUP_WINDOW);
+            let (plot_dir, svg) = plot_dir(&mut args);
+            args.finish();
+            let settings = (replications, interval, window);
+            let model = load_model(&scenario);
+            warmup_analysis(&scenario, &model, settings, plot_dir.as_deref(), svg);
+        }
+        "batch" => {
+            let batching = args.choice::<Batching>("--by").unwrap_or(Batching::Time);
+            let expected = format!("at least {COMPONENT_COUNT}");
+            let components = args
+                .parse("--components", |c| *c >= COMPONENT_COUNT, &expected)
+                .unwrap_or(BATCH_COMPONENTS);
+            let batches = args
+                .parse("--batches", |b| *b >= 10, "at least 10")
+                .unwrap_or(80);
+            args.finish();
+            let run = (components, batches);
+            batch_means(&scenario, &load_model(&scenario), batching, run);
+        }
+        "optimize" => {
+            // i.e optimize --places 12 --search greedy
+            let minimum = topology
+                .buffers()
+                .iter()
+                .map(|b| b.needed)
+                .collect::<Vec<usize>>();
+            let least = minimum.iter().sum::<usize>();
+            let expected = format!("at least {least}, enough for a product in each buffer");
+            let total = args
+                .parse("--places", |p| *p >= least, &expected)
+                .unwrap_or(least.max(10));
+            // exhaustive unless there are too many allocations
+            let search = match args.value("--search").as_deref() {
+                None | Some("auto") => {
+                    match allocation::count(total, &minimum) <= EXHAUSTIVE_ALLOCATIONS {
+                        true => Search::Exhaustive,
+                        false => Search::Greedy,
+                    }
+                }
+                Some(s) => match s.parse::<Search>() {
+                    Ok(search) => search,
+                    Err(e) => fail(e),
+                },
+            };
+            let replications = replications(&mut args, 2, scenario.generator).unwrap_or(INIT_R);
+            let top = args
+                .parse("--top", |t| *t >= 1, "a positive number of allocations")
+                .unwrap_or(10);
+            args.finish();
+            let settings = (total, search, replications, top);
+            optimize(&scenario, &load_model(&scenario), settings);
+        }
+        "fit" => {
+            let dir = data_dir(&mut args);
+            let alpha = args
+                .parse(
+                    "--alpha",
+                    |a| *a > 0.0 && *a < 1.0,
+                    "a significance level between 0 and 1",
+                )
+                .unwrap_or(0.05);
+            let (plot_dir, svg) = plot_dir(&mut args);
+            args.finish();
+            fit(&dir, alpha, plot_dir.as_deref(), svg, format);
+        }
+        "run" => {
+            // a fixed number of replications in place of the stopping rule
+            let scenario = match replications(&mut args, 2, scenario.generator) {
+                None => scenario.clone(),
+                Some(r) => Scenario {
+                    replications: (r, r),
+                    ..scenario.clone()
+                },
+            };
+            args.finish();
+            run_replications(&scenario, &load_model(&scenario), format);
+        }
+        other => fail(format!("unknown command: {other}\n\n{USAGE}")),
+    }
+}
+
+fn batch_means(
+    scenario: &Scenario,
+    model: &InputModel,
+    batching: Batching,
+    (components, batches): (usize, usize),
+) {
+    // one long run after the warm-up, cut into batches. the batches
+    // are merged in pairs until the lag-1 autocorrelation of every
+    // measure's batch means is acceptable, or too few are left
+    const CONFIDENCE: f64 = 0.95;
+    const MIN_BATCHES: usize = 10;
+    let mut config = RunConfig {
+        components,
+        ..RunConfig::new(scenario, ServiceTimes::Generated(model))
+    };
+    resolve_warmup(&mut config, scenario.warmup);
+    let mut facility = Facility::generate(&config, 0);
+    facility.simulation.run();
+    let end = end_time(&facility);
+    let mut completions = facility
+        .ws
+        .iter()
+        .flat_map(|ws| {
+            ws.borrow()
+                .products
+                .iter()
+                .map(|p| p.timestamp().get())
+                .collect::<Vec<f64>>()
+        })
+        .collect::<Vec<f64>>();
+    completions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let measures = Measure::all(&model.topology);
+    let mut k = batches;
+    let (means, correlations) = loop {
+        let bounds = match batch::bounds(batching, config.warmup, end, &completions, k) {
+            Ok(bounds) => bounds,
+            Err(e) => fail(e),
+        };
+        let means = batch_series(&facility, &bounds)
+            .into_iter()
+            .collect::<ResultsAggregator>();
+        let correlations = measures
+            .iter()
+            .map(|m| batch::lag1_autocorrelation(&means.values(*m)))
+            .collect::<Vec<f64>>();
+        if correlations.iter().all(|r| batch::acceptable(*r, k)) || k / 2 < MIN_BATCHES {
+            break (means, correlations);
+        }
+        k /= 2;
+    };
+
+    println!(
+        "Batch means of one run of {components} components per stream \
+        from {} to {end:.0} minutes:",
+        config.warmup
+    );
+    let after = completions.iter().filter(|t| **t > config.warmup).count();
+    match batching {
+        Batching::Time => println!(
+            "{k} batches of {:.1} minutes",
+            (end - config.warmup) / k as f64
+        ),
+        Batching::Products => println!("{k} batches of {} products", after / k),
+    }
+    println!("{:<26} {:>10} {:>10} {:>10}", "", "mean", "+-", "lag-1 r");
+    for (i, measure) in measures.iter().enumerate() {
+        let interval = means.interval(*measure, CONFIDENCE).unwrap();
+        println!(
+            "{:<26} {:>10.4} {:>10.4} {:>10.3} {}",
+            measure.name(&model.topology),
+            interval.mean,
+            interval.half_width,
+            correlations[i],
+            match batch::acceptable(correlations[i], k) {
+                true => "",
+                false => "correlated",
+            }
+        );
+    }
+    if !correlations.iter().all(|r| batch::acceptable(*r, k)) {
+        println!(
+            "\nSome batch means are still correlated with {k} batches, \
+            so the run needs more --components"
+        );
+    }
+}
+
+fn load_traces(dir: &Path) -> Traces {
+    match Traces::load(dir) {
+        Ok(traces) => traces,
+        Err(e) => fail(e),
+    }
+}
+
+fn replay(scenario: &Scenario, dir: &Path) {
+    // runs a single replication using the observed service times
+    // so the output can be checked against the fitted model
+    let topology = &scenario.topology;
+    if !topology.traced {
+        fail("replay needs observed service times, which only the built-in facility has");
+    }
+    let traces = load_traces(dir);
+    let mut config = RunConfig::new(scenario, ServiceTimes::Trace(&traces));
+    resolve_warmup(&mut config, scenario.warmup);
+    let stats = measured(run_iteration(&config, 0));
+
+    println!("Replayed service times from {}", dir.display());
+    print_replication(topology, &stats);
+}
+
+fn print_replication(topology: &Topology, result: &ReplicationResult) {
+    println!("\nAverage occupancy for each buffer:");
+    for (h, occupancy) in topology.buffer_names().iter().zip(result.occupancy.iter()) {
+        println!("{} {:.2}", h, occupancy);
+    }
+    println!(
+        "\n{} busy ratio : {:.2?}",
+        bracketed(topology.workstations.iter().map(|ws| &ws.name)),
+        result.utilization
+    );
+    println!(
+        "{} throughput : {:.4?}",
+        bracketed(topology.products.iter().map(|p| &p.name)),
+        result.throughput
+    );
+    println!(
+        "{} blocked ratio: {:.4?}",
+        bracketed(topology.inspectors.iter().map(|i| &i.name)),
+        result.blocking
+    );
+    println!("Total Average Occupancy: {:.4}", result.wip);
+}
+
+fn fit(dir: &Path, alpha: f64, plot_dir: Option<&Path>, svg: bool, format: Format) {
+    // fits every candidate distribution to each data file,
+    // prints the parameters with confidence intervals and then
+    // tests each fit at the significance level alpha.
+    // if plot_dir is given the plot data is written there too
+    const CONFIDENCE: f64 = 0.95;
+    let traces = load_traces(dir);
+    if format == Format::Csv {
+        println!(
+            "{}",
+            report::csv([
+                "stream",
+                "n",
+                "family",
+                "log-likelihood",
+                "AIC",
+                "best",
+                "parameters",
+                "chi-square",
+                "df",
+                "chi-square p",
+                "K-S D",
+                "K-S p"
+            ])
+        );
+    }
+
+    for (name, durations) in traces.named() {
+        let data = trace::as_minutes(durations);
+        let fits = fitting::fit_all(&data);
+        let best = fits
+            .iter()
+            .min_by(|a, b| a.aic().partial_cmp(&b.aic()).unwrap())
+            .map(|fit| fit.family);
+
+        let bins = gof::equiprobable_bins(data.len());
+        match format {
+            Format::Text => {
+                println!("\n{name} (n = {})", data.len());
+                for fit in fits.iter() {
+                    println!(
+                        "  {:<12} log-likelihood {:>10.2} | AIC {:>9.2}{}",
+                        fit.family.name(),
+                        fit.log_likelihood,
+                        fit.aic(),
+                        match Some(fit.family) == best {
+                            true => " (best)",
+                            false => "",
+                        }
+                    );
+                    for p in fit.params.iter() {
+                        let (low, high) = p.interval(CONFIDENCE);
+                        println!(
+                            "      {:<9} {:>10.5} | {:.0}% CI [{:.5}, {:.5}]",
+                            p.name,
+                            p.value,
+                            CONFIDENCE * 100.0,
+                            low,
+                            high
+                        );
+                    }
+                }
+
+                let verdict = |test: &gof::TestResult| match test.accepts(alpha) {
+                    true => "accept",
+                    false => "reject",
+                };
+                println!(
+                    "  goodness of fit at α = {alpha} ({bins} equiprobable bins)\n  \
+            {:<12} {:>9} {:>3} {:>7}        | {:>7} {:>7}",
+                    "", "χ²", "df", "p", "K-S D", "p"
+                );
+                for fit in fits.iter() {
+                    let chi = gof::chi_square(&data, fit, bins);
+                    let ks = gof::kolmogorov_smirnov(&data, fit);
+                    println!(
+                        "  {:<12} {:>9.3} {:>3} {:>7.4} {} | {:>7.4} {:>7.4} {}",
+                        fit.family.name(),
+                        chi.statistic,
+                        gof::degrees_of_freedom(fit, bins),
+                        chi.p_value,
+                        verdict(&chi),
+                        ks.statistic,
+                        ks.p_value,
+                        verdict(&ks)
+                    );
+                }
+            }
+            Format::Csv => {
+                for fit in fits.iter() {
+                    let chi = gof::chi_square(&data, fit, bins);
+                    let ks = gof::kolmogorov_smirnov(&data, fit);
+                    let parameters = fit
+                        .params
+                        .iter()
+                        .map(|p| format!("{}={}", p.name, p.value))
+                        .collect::<Vec<String>>();
+                    println!(
+                        "{}",
+                        report::csv([
+                            name.to_string(),
+                            data.len().to_string(),
+                            fit.family.name().to_string(),
+                            fit.log_likelihood.to_string(),
+                            fit.aic().to_string(),
+                            (Some(fit.family) == best).to_string(),
+                            parameters.join(" "),
+                            chi.statistic.to_string(),
+                            gof::degrees_of_freedom(fit, bins).to_string(),
+                            chi.p_value.to_string(),
+                            ks.statistic.to_string(),
+                            ks.p_value.to_string()
+                        ])
+                    );
+                }
+            }
+        }
+
+        if let Some(plot_dir) = plot_dir {
+            let stem = name.trim_end_matches(".dat");
+            match plots::write_plots(plot_dir, stem, &data, &fits, svg) {
+                // kept out of the CSV
+                Ok(written) if format == Format::Csv => eprintln!(
+                    "wrote {} plot files to {}",
+                    written.len(),
+                    plot_dir.display()
+                ),
+                Ok(written) => println!(
+                    "  wrote {} plot files to {}",
+                    written.len(),
+                    plot_dir.display()
+                ),
+                Err(e) => {
+                    fail(format!(
+                        "could not write plots to {}: {e}",
+                        plot_dir.display()
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn rngtest(
+    generator: Generator,
+    seed: u32,
+    substreams: usize,
+    (samples, lags, alpha): (usize, &[usize], f64),
+    format: Format,
+) {
+    // runs the empirical test battery on the first stream of one
+    // generator, in each of the substreams the first replications draw from
+    let outcomes = (0..substreams)
+        .map(|substream| {
+            let mut rand = Random::streams(generator, seed, 1).remove(0);
+            if let Err(e) = rand.advance_substreams(substream as u64) {
+                fail(e);
+            }
+            rngtest::battery(&mut rand, samples, lags)
+        })
+        .collect::<Vec<Vec<rngtest::Outcome>>>();
+    let verdict = |accepts: bool| match accepts {
+        true => "pass",
+        false => "FAIL",
+    };
+    let spectral = match generator {
+        Generator::Lcg => {
+            let (a, m) = random::lcg_parameters(seed);
+            Some((a, m, rngtest::spectral(a, m, 6)))
+        }
+        _ => None,
+    };
+    match format {
+        Format::Text => {
+            println!(
+                "Test battery for {generator} with seed {seed}{}, \
+                {samples} numbers per test at alpha = {alpha}:",
+                match substreams {
+                    1 => String::new(),
+                    n => format!(" in {n} substreams"),
+                }
+            );
+            for (i, outcome) in outcomes[0].iter().enumerate() {
+                match substreams {
+                    1 => println!(
+                        "{:<28} statistic {:>12.4}  p {:.4}  {}",
+                        outcome.name,
+                        outcome.result.statistic,
+                        outcome.result.p_value,
+                        verdict(outcome.result.accepts(alpha))
+                    ),
+                    n => {
+                        let results = outcomes.iter().map(|o| &o[i].result);
+                        let passed = results.clone().filter(|r| r.accepts(alpha)).count();
+                        let smallest = results.map(|r| r.p_value).fold(1.0, f64::min);
+                        println!(
+                            "{:<28} passed in {passed} of {n}  smallest p {smallest:.4}",
+                            outcome.name
+                        );
+                    }
+                }
+            }
+            let all = outcomes.iter().flatten();
+            let passed = all.clone().filter(|o| o.result.accepts(alpha)).count();
+            println!("{passed} of {} tests passed", all.count());
+
+            if let Some((a, m, results)) = spectral {
+                println!("\nSpectral test of a = {a}, m = {m}:");
+                println!("t   nu_t            1/nu_t        mu_t");
+                for result in results {
+                    println!(
+                        "{}   {:<14.1}  {:<12.4e}  {:.4}  {}",
+                        result.dimension,
+                        result.nu,
+                        1.0 / result.nu,
+                        result.merit,
+                        verdict(result.accepts())
+                    );
+                }
+            }
+        }
+        Format::Csv => {
+            // the spectral test's figure of merit is its statistic
+            println!(
+                "{}",
+                report::csv(["substream", "test", "statistic", "p", "result"])
+            );
+            for (substream, outcomes) in outcomes.iter().enumerate() {
+                for outcome in outcomes {
+                    println!(
+                        "{}",
+                        report::csv([
+                            substream.to_string(),
+                            outcome.name.clone(),
+                            outcome.result.statistic.to_string(),
+                            outcome.result.p_value.to_string(),
+                            verdict(outcome.result.accepts(alpha)).to_string()
+                        ])
+                    );
+                }
+            }
+            for result in spectral.map(|(_, _, results)| results).unwrap_or_default() {
+                println!(
+                    "{}",
+                    report::csv([
+                        String::new(),
+                        format!("spectral t = {}", result.dimension),
+                        result.merit.to_string(),
+                        String::new(),
+                        verdict(result.accepts()).to_string()
+                    ])
+                );
+            }
+        }
+    }
+}
+
+fn load_model(scenario: &Scenario) -> InputModel {
+    // only the built-in facility has observed times to fit to
+    let topology = &scenario.topology;
+    let traces = topology
+        .traced
+        .then(|| load_traces(Path::new(trace::DATA_DIR)));
+    match InputModel::new(topology.clone(), traces.as_ref(), &scenario.services) {
+        Ok(model) => model,
+        Err(e) => fail(e),
+    }
+}
+
+fn checkpoint(path: &Path, at: f64, replication: u64, scenario: &Scenario, model: &InputModel) {
+    // runs a replication up to the time at, then saves it so
+    // it can be resumed from just before the next event
+    let config = RunConfig::new(scenario, ServiceTimes::Generated(model));
+    let mut facility = Facility::generate(&config, replication);
+    facility.simulation.start();
+    facility
+        .simulation
+        .run_until(TimeStamp::start() + Duration::of_minutes(at));
+
+    let mut out = Writer::default();
+    facility.save(&mut out);
+    if let Err(e) = out.write(path) {
+        fail(e);
+    }
+    println!(
+        "Saved replication {replication} at {} minutes to {}",
+        facility.simulation.clock(),
+        path.display()
+    );
+}
+
+fn resume(topology: Rc<Topology>, path: &Path, warmup: f64) {
+    // restores a checkpoint of the same facility into an
+    // empty one and runs it to completion
+    let mut facility = Facility::empty(topology.clone());
+    if let Err(e) =
+        Reader::read(path, topology.clone()).and_then(|mut input| facility.restore(&mut input))
+    {
+        fail(e);
+    }
+    println!(
+        "Resumed {} at {} minutes",
+        path.display(),
+        facility.simulation.clock()
+    );
+    facility.simulation.resume();
+    print_replication(&topology, &measured(facility_stats(&facility, warmup)));
+}
+
+fn warmup_analysis(
+    scenario: &Scenario,
+    model: &InputModel,
+    (replications, interval, window): (usize, f64, usize),
+    plot_dir: Option<&Path>,
+    svg: bool,
+) {
+    // suggests a warm-up from the Welch moving averages of the
+    // output over the replications and the MSER-5 heuristic.
+    // if plot_dir is given the Welch plot data is written there
+    let config = RunConfig::new(scenario, ServiceTimes::Generated(model));
+    let (welch, truncation) = detect_warmup(&config, replications, interval, window);
+    let recommended = truncation.into_iter().fold(0.0, f64::max);
+
+    println!(
+        "Warm-up over {replications} replications in {interval} minute \
+        intervals, with a Welch window of {window}:"
+    );
+    for (name, minutes) in SERIES.iter().zip(truncation) {
+        println!("{name:<18} MSER-5 deletes {minutes} minutes");
+    }
+    println!(
+        "Recommended warm-up: {recommended} minutes \
+        (apply it with --warmup {recommended} or --warmup auto)"
+    );
+
+    // about twenty rows of the moving averages
+    let length = welch.iter().map(|w| w.len()).min().unwrap_or(0);
+    println!("\n{:>10} {:>18} {:>18}", "minutes", SERIES[0], SERIES[1]);
+    for i in (0..length).step_by((length / 20).max(1)) {
+        println!(
+            "{:>10.0} {:>18.5} {:>18.4}",
+            (i as f64 + 0.5) * interval,
+            welch[0][i],
+            welch[1][i]
+        );
+    }
+
+    if let Some(dir) = plot_dir {
+        match plots::write_welch(dir, &SERIES, interval, &welch, recommended, svg) {
+            Ok(written) => println!("\nwrote {} plot files to {}", written.len(), dir.display()),
+            Err(e) => {
+                fail(format!("could not write plots to {}: {e}", dir.display()));
+            }
+        }
+    }
+}
+
+fn compare(what: &str, scenarios: &[(String, Scenario)], replications: usize, format: Format) {
+    // runs every scenario on the same streams in each replication
+    // (common random numbers), so the differences between them come
+    // from the scenarios rather than from the service times drawn.
+    // each is compared with the first, which gives the measures
+    const CONFIDENCE: f64 = 0.95;
+    let models = scenarios
+        .iter()
+        .map(|(_, scenario)| load_model(scenario))
+        .collect::<Vec<InputModel>>();
+    let mut configs = scenarios
+        .iter()
+        .zip(models.iter())
+        .map(|((_, scenario), model)| RunConfig::new(scenario, ServiceTimes::Generated(model)))
+        .collect::<Vec<RunConfig>>();
+    // every scenario deletes the same warm-up, the longest of them
+    let warmup = scenarios
+        .iter()
+        .zip(configs.iter())
+        .map(|((_, scenario), config)| scenario.warmup.unwrap_or_else(|| auto_warmup(config)))
+        .fold(0.0, f64::max);
+    for config in configs.iter_mut() {
+        config.warmup = warmup;
+    }
+    let results = configs
+        .iter()
+        .map(|config| {
+            (0..replications)
+                .map(|r| measured(run_iteration(config, r as u64)))
+                .collect::<ResultsAggregator>()
+        })
+        .collect::<Vec<ResultsAggregator>>();
+
+    let (first, topology) = (&scenarios[0].0, &scenarios[0].1.topology);
+    if format == Format::Csv {
+        println!(
+            "{}",
+            report::csv([
+                "scenario",
+                "against",
+                "measure",
+                "mean",
+                "against mean",
+                "difference",
+                "half width",
+                "significant"
+            ])
+        );
+    }
+    for (i, ((other, _), other_results)) in scenarios.iter().zip(results.iter()).enumerate().skip(1)
+    {
+        if format == Format::Text {
+            println!(
+                "{}{what} over {replications} paired replications, \
+                {first} - {other} with {}% paired-t intervals:",
+                match i {
+                    1 => "",
+                    _ => "\n",
+                },
+                CONFIDENCE * 100.0
+            );
+            println!(
+                "{:<26} {:>10} {:>10} {:>11} {:>10}",
+                "", first, other, "difference", "+-"
+            );
+        }
+        for measure in Measure::all(topology) {
+            let (a, b) = (results[0].values(measure), other_results.values(measure));
+            let d = statistics::paired_difference(&a, &b, CONFIDENCE).unwrap();
+            match format {
+                Format::Text => println!(
+                    "{:<26} {:>10.4} {:>10.4} {:>11.4} {:>10.4} {}",
+                    measure.name(topology),
+                    statistics::mean(&a),
+                    statistics::mean(&b),
+                    d.mean,
+                    d.half_width,
+                    match d.contains(0.0) {
+                        true => "",
+                        false => "significant",
+                    }
+                ),
+                Format::Csv => println!(
+                    "{}",
+                    report::csv([
+                        first.clone(),
+                        other.clone(),
+                        measure.name(topology),
+                        statistics::mean(&a).to_string(),
+                        statistics::mean(&b).to_string(),
+                        d.mean.to_string(),
+                        d.half_width.to_string(),
+                        (!d.contains(0.0)).to_string()
+                    ])
+                ),
+            }
+        }
+    }
+}
+
+fn sweep(
+    names: &[String],
+    points: &[(Vec<String>, Scenario)],
+    replications: usize,
+    format: Format,
+) {
+    // runs every point of the grid on the same streams (common random
+    // numbers), so that neighbouring points differ by their settings
+    // rather than by the service times drawn
+    const CONFIDENCE: f64 = 0.95;
+    let topology = &points[0].1.topology;
+    // each setting's column is as wide as its name or widest value
+    let widths = (0..names.len())
+        .map(|i| {
+            points
+                .iter()
+                .map(|(values, _)| values[i].len())
+                .fold(names[i].len(), usize::max)
+        })
+        .collect::<Vec<usize>>();
+    let settings = |values: &[String]| {
+        values
+            .iter()
+            .zip(widths.iter())
+            .map(|(value, width)| format!("{value:<width$}"))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    match format {
+        Format::Text => {
+            println!(
+                "Sweeping {} over {} points with {replications} replications \
+                each, with {}% intervals:",
+                names.join(", "),
+                points.len(),
+                CONFIDENCE * 100.0
+            );
+            println!(
+                "{} {:>10} {:>10} {:>10} {:>10}",
+                settings(names),
+                "throughput",
+                "+-",
+                "occupancy",
+                "+-"
+            );
+        }
+        Format::Csv => {
+            let measures = Measure::all(topology)
+                .iter()
+                .flat_map(|measure| {
+                    let name = measure.name(topology);
+                    [name.clone(), format!("{name} half width")]
+                })
+                .collect::<Vec<String>>();
+            let columns = ["throughput", "throughput half width"].map(String::from);
+            println!(
+                "{}",
+                report::csv(names.iter().chain(columns.iter()).chain(measures.iter()))
+            );
+        }
+    }
+
+    for (values, scenario) in points {
+        let model = load_model(scenario);
+        let mut config = RunConfig::new(scenario, ServiceTimes::Generated(&model));
+        resolve_warmup(&mut config, scenario.warmup);
+        let results = (0..replications)
+            .map(|r| measured(run_iteration(&config, r as u64)))
+            .collect::<Vec<ReplicationResult>>();
+        let throughput = results
+            .iter()
+            .map(|result| result.total_throughput(topology))
+            .collect::<Vec<f64>>();
+        let throughput = ConfidenceInterval::from_sample(&throughput, CONFIDENCE).unwrap();
+        let results = results.into_iter().collect::<ResultsAggregator>();
+        let cis = Measure::all(topology)
+            .into_iter()
+            .map(|measure| results.interval(measure, CONFIDENCE).unwrap())
+            .collect::<Vec<ConfidenceInterval>>();
+        match format {
+            Format::Text => {
+                let wip = results.interval(Measure::Wip, CONFIDENCE).unwrap();
+                println!(
+                    "{} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                    settings(values),
+                    throughput.mean,
+                    throughput.half_width,
+                    wip.mean,
+                    wip.half_width
+                );
+            }
+            Format::Csv => {
+                let estimates = [throughput]
+                    .iter()
+                    .chain(cis.iter())
+                    .flat_map(|ci| [ci.mean.to_string(), ci.half_width.to_string()])
+                    .collect::<Vec<String>>();
+                println!("{}", report::csv(values.iter().chain(estimates.iter())));
+            }
+        }
+    }
+}
+
+fn trace_replication(
+    scenario: &Scenario,
+    model: &InputModel,
+    replication: u64,
+    until: Option<f64>,
+    format: Format,
+) {
+    // the event log of one replication, up to until if given: every
+    // event broadcast and the state of each actor after it acted
+    let config = RunConfig::new(scenario, ServiceTimes::Generated(model));
+    let mut facility = Facility::generate(&config, replication);
+    facility.simulation.keep_log();
+    facility.simulation.start();
+    match until {
+        Some(until) => facility
+            .simulation
+            .run_until(TimeStamp::start() + Duration::of_minutes(until)),
+        None => {
+            facility.simulation.resume();
+        }
+    }
+    let log = facility.simulation.event_log();
+    match format {
+        Format::Text => {
+            for (time, entry) in log {
+                println!("{:>10.2} {entry}", time.get());
+            }
+            println!(
+                "{} entries in replication {replication} up to {} minutes",
+                log.len(),
+                facility.simulation.clock()
+            );
+        }
+        Format::Csv => {
+            println!("{}", report::csv(["time", "entry"]));
+            for (time, entry) in log {
+                println!("{}", report::csv([time.get().to_string(), entry.clone()]));
+            }
+        }
+    }
+}
+
+fn antithetic(scenario: &Scenario, model: &InputModel, pairs: usize) {
+    // estimates every measure twice with the same number of runs: from
+    // 2 * pairs independent replications, and from pairs antithetic
+    // pairs, each pooling a replication with its mirror image. the
+    // negative correlation within a pair is what reduces the variance,
+    // though rejection sampling (i.e gamma service times) weakens it
+    const CONFIDENCE: f64 = 0.95;
+    let mut config = RunConfig::new(scenario, ServiceTimes::Generated(model));
+    resolve_warmup(&mut config, scenario.warmup);
+    let run = |antithetic: bool, replications: usize| {
+        let config = RunConfig {
+            antithetic,
+            ..config.clone()
+        };
+        (0..replications)
+            .map(|r| measured(run_iteration(&config, r as u64)))
+            .collect::<ResultsAggregator>()
+    };
+    // the first half of the independent replications
+    // are also the first member of every pair
+    let independent = run(false, 2 * pairs);
+    let mirrored = run(true, pairs);
+
+    println!(
+        "{} independent replications against {pairs} antithetic pairs, \
+        with {}% intervals:",
+        2 * pairs,
+        CONFIDENCE * 100.0
+    );
+    println!(
+        "{:<26} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "", "independent", "+-", "antithetic", "+-", "reduction"
+    );
+    for measure in Measure::all(&model.topology) {
+        let independent = independent.values(measure);
+        let pooled = independent[..pairs]
+            .iter()
+            .zip(mirrored.values(measure))
+            .map(|(a, b)| (a + b) / 2.0)
+            .collect::<Vec<f64>>();
+        let plain = ConfidenceInterval::from_sample(&independent, CONFIDENCE).unwrap();
+        let antithetic = ConfidenceInterval::from_sample(&pooled, CONFIDENCE).unwrap();
+        // compares the variances of the two estimators of the mean
+        let (plain_variance, pooled_variance) = (
+            statistics::variance(&independent) / (2 * pairs) as f64,
+            statistics::variance(&pooled) / pairs as f64,
+        );
+        let reduction = match plain_variance > 0.0 {
+            true => 1.0 - pooled_variance / plain_variance,
+            false => 0.0,
+        };
+        println!(
+            "{:<26} {:>11.4} {:>10.4} {:>10.4} {:>10.4} {:>9.1}%",
+            measure.name(&model.topology),
+            plain.mean,
+            plain.half_width,
+            antithetic.mean,
+            antithetic.half_width,
+            100.0 * reduction
+        );
+    }
+}
+
+fn optimize(
+    scenario: &Scenario,
+    model: &InputModel,
+    (total, search, replications, top): (usize, Search, usize, usize),
+) {
+    // splits total places across the buffers to maximise the throughput.
+    // every allocation is run on the same streams (common random numbers),
+    // so the differences from the best are paired like those of compare
+    const CONFIDENCE: f64 = 0.95;
+    // every buffer keeps enough for a product, and the warm-up
+    // is detected with the places beyond those spread evenly
+    let minimum = model
+        .topology
+        .buffers()
+        .iter()
+        .map(|b| b.needed)
+        .collect::<Allocation>();
+    let (n, spare) = (minimum.len(), total - minimum.iter().sum::<usize>());
+    let even = (0..n)
+        .map(|i| minimum[i] + spare / n + usize::from(i < spare % n))
+        .collect::<Allocation>();
+    let mut config = RunConfig {
+        capacities: &even,
+        ..RunConfig::new(scenario, ServiceTimes::Generated(model))
+    };
+    resolve_warmup(&mut config, scenario.warmup);
+    println!(
+        "Splitting {total} places across {} by {search} search, \
+        with {replications} replications of each allocation",
+        model.topology.buffer_names().join(", ")
+    );
+
+    // the throughput of every replication of each allocation
+    // evaluated, so that no allocation is run twice
+    let mut evaluated: Vec<(Allocation, Vec<f64>)> = vec![];
+    let mut evaluate = |capacities: &Allocation| {
+        if let Some((_, values)) = evaluated.iter().find(|(a, _)| a == capacities) {
+            return statistics::mean(values);
+        }
+        let config = RunConfig {
+            capacities,
+            ..config.clone()
+        };
+        let values = (0..replications)
+            .map(|r| measured(run_iteration(&config, r as u64)).total_throughput(&model.topology))
+            .collect::<Vec<f64>>();
+        let mean = statistics::mean(&values);
+        evaluated.push((capacities.clone(), values));
+        mean
+    };
+    match search {
+        Search::Exhaustive => {
+            for capacities in allocation::allocations(total, &minimum) {
+                evaluate(&capacities);
+            }
+        }
+        Search::Greedy => {
+            let last = allocation::greedy(total, &minimum, &mut evaluate);
+            // with no places to spare there were no candidates
+            evaluate(&last);
+        }
+    }
+
+    // the greedy search also ran allocations of fewer places on its way
+    evaluated.retain(|(capacities, _)| capacities.iter().sum::<usize>() == total);
+    evaluated.sort_by(|(_, a), (_, b)| {
+        statistics::mean(b)
+            .partial_cmp(&statistics::mean(a))
+            .unwrap()
+    });
+    let best = evaluated[0].1.clone();
+    println!(
+        "\nThe best {} of {} allocations by throughput, with {}% intervals \
+        and paired-t intervals for their difference from the best:",
+        top.min(evaluated.len()),
+        evaluated.len(),
+        CONFIDENCE * 100.0
+    );
+    println!(
+        "{:>4} {:<16} {:>10} {:>10} {:>11} {:>10}",
+        "rank", "capacities", "throughput", "+-", "difference", "+-"
+    );
+    for (rank, (capacities, values)) in evaluated.iter().take(top).enumerate() {
+        let ci = ConfidenceInterval::from_sample(values, CONFIDENCE).unwrap();
+        let capacities = capacities
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        match rank {
+            0 => println!(
+                "{:>4} {:<16} {:>10.4} {:>10.4} {:>11} {:>10}",
+                rank + 1,
+                capacities,
+                ci.mean,
+                ci.half_width,
+                "-",
+                "-"
+            ),
+            _ => {
+                let d = statistics::paired_difference(values, &best, CONFIDENCE).unwrap();
+                println!(
+                    "{:>4} {:<16} {:>10.4} {:>10.4} {:>11.4} {:>10.4} {}",
+                    rank + 1,
+                    capacities,
+                    ci.mean,
+                    ci.half_width,
+                    d.mean,
+                    d.half_width,
+                    match d.contains(0.0) {
+                        true => "",
+                        false => "significant",
+                    }
+                );
+            }
+        }
+    }
+}
+
+fn controlled(
+    model: &InputModel,
+    results: &ResultsAggregator,
+    controls: &[Vec<f64>],
+    plain: &[ConfidenceInterval],
+) -> Vec<Option<ConfidenceInterval>> {
+    // the occupancy and throughput estimates adjusted by the sample
+    // mean service times, whose true means the input model knows.
+    // the ratios are left out, as are any the regression fails for
+    let means = model
+        .assembly
+        .iter()
+        .chain(model.inspection.iter())
+        .map(|distribution| distribution.mean())
+        .collect::<Vec<f64>>();
+    Measure::all(&model.topology)
+        .into_iter()
+        .zip(plain)
+        .map(|(measure, plain)| match measure.is_ratio() {
+            true => None,
+            false => control::control_variates(
+                &results.values(measure),
+                controls,
+                &means,
+                plain.confidence,
+            ),
+        })
+        .collect()
+}
+
+fn print_controlled(
+    topology: &Topology,
+    plain: &[ConfidenceInterval],
+    controlled: &[Option<ConfidenceInterval>],
+) {
+    // next to the plain estimates and interval half widths
+    println!("\nControl variates (sample mean service times):");
+    println!(
+        "{:<26} {:>10} {:>10} {:>10} {:>10}",
+        "", "plain", "+-", "controlled", "+-"
+    );
+    // the buffer, throughput and total occupancy measures
+    for ((measure, plain), controlled) in Measure::all(topology)
+        .into_iter()
+        .zip(plain)
+        .zip(controlled)
+    {
+        if measure.is_ratio() {
+            continue;
+        }
+        match controlled {
+            Some(controlled) => println!(
+                "{:<26} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                measure.name(topology),
+                plain.mean,
+                plain.half_width,
+                controlled.mean,
+                controlled.half_width
+            ),
+            None => println!(
+                "{:<26} {:>10.4} {:>10.4} {:>10} {:>10}",
+                measure.name(topology),
+                plain.mean,
+                plain.half_width,
+                "-",
+                "-"
+            ),
+        }
+    }
+}
+
+fn run_replications(scenario: &Scenario, model: &InputModel, format: Format) {
+    log!(
+        "{}",
+        model
+            .topology
+            .services()
+            .iter()
+            .zip(model.assembly.iter().chain(model.inspection.iter()))
+            .map(|(service, distribution)| format!(
+                "{} service times ~ {distribution}",
+                service.stream
+            ))
+            .collect::<Vec<String>>()
+            .join("\n")
+    );
+    let mut config = RunConfig::new(scenario, ServiceTimes::Generated(model));
+    resolve_warmup(&mut config, scenario.warmup);
+
+    // each measure's interval at the confidence level of its target
+    let (initial, max) = scenario.replications;
+    let targets = &scenario.targets;
+    let measures = Measure::all(&model.topology);
+    let intervals = |results: &ResultsAggregator| {
+        measures
+            .iter()
+            .zip(targets)
+            .map(|(measure, target)| results.interval(*measure, target.confidence))
+            .collect::<Option<Vec<ConfidenceInterval>>>()
+    };
+    let mut results = ResultsAggregator::default();
+    // the sample mean service times of every replication
+    let mut controls: Vec<Vec<f64>> = vec![];
+    let mut converged = false;
+
+    for r in 0..max {
+        let mut facility = Facility::generate(&config, r as u64);
+        facility.simulation.run();
+        let result = measured(facility_stats(&facility, config.warmup));
+        controls.push(facility.service_means.to_vec());
+        // the progress is left out of CSV
+        if format == Format::Text {
+            println!("{} \t {}", r + 1, result.total_throughput(&model.topology));
+        }
+        results.push(result);
+
+        // the sequential stopping rule: once there are enough replications
+        // to trust the variances, stop when every interval meets its target
+        let Some(cis) = intervals(&results) else {
+            continue;
+        };
+        let waiting = (0..measures.len())
+            .filter(|i| !targets[*i].met(&cis[*i]))
+            .map(|i| {
+                let precision = targets[i].precision;
+                let achieved = precision.achieved(&cis[i]);
+                format!(
+                    "{} ({achieved:.4} for {precision})",
+                    measures[i].name(&model.topology)
+                )
+            })
+            .collect::<Vec<String>>();
+        if !waiting.is_empty() && format == Format::Text {
+            println!("  waiting on {}", waiting.join(", "));
+        }
+        converged = r + 1 >= initial && waiting.is_empty();
+        if converged {
+            break;
+        }
+    }
+    let n = results.len();
+    let cis = intervals(&results).unwrap();
+    let controlled = controlled(model, &results, &controls, &cis);
+    if format == Format::Csv {
+        match converged {
+            true => eprintln!("Converged on replication count (R) of {n}"),
+            false => eprintln!("Did not converge within {max} replications"),
+        }
+        println!(
+            "{}",
+            report::csv([
+                "measure",
+                "mean",
+                "half width",
+                "confidence",
+                "controlled mean",
+                "controlled half width"
+            ])
+        );
+        for ((measure, ci), controlled) in measures.iter().zip(cis.iter()).zip(controlled) {
+            let (controlled_mean, controlled_half_width) = match controlled {
+                Some(c) => (c.mean.to_string(), c.half_width.to_string()),
+                None => (String::new(), String::new()),
+            };
+            println!(
+                "{}",
+                report::csv([
+                    measure.name(&model.topology),
+                    ci.mean.to_string(),
+                    ci.half_width.to_string(),
+                    ci.confidence.to_string(),
+                    controlled_mean,
+                    controlled_half_width
+                ])
+            );
+        }
+        return;
+    }
+    match converged {
+        true => println!("\nConverged on replication count (R) of {n}"),
+        false => println!("\nDid not converge within {max} replications"),
+    }
+    let mut mean = ReplicationResult::new(&model.topology);
+    let mut half_width = ReplicationResult::new(&model.topology);
+    for (measure, ci) in measures.iter().zip(cis.iter()) {
+        mean.set(*measure, ci.mean);
+        half_width.set(*measure, ci.half_width);
+    }
+
+    log!(
+        "\nAverages for {n} replications with a \
+        queue size of {} each",
+        config.components
+    );
+    log!("\nAverage occupancy for each buffer:");
+    for (i, h) in model.topology.buffer_names().iter().enumerate() {
+        println!(
+            "{} {:#.2?} +- {:.5}",
+            h, mean.occupancy[i], half_width.occupancy[i]
+        );
+    }
+    log!(
+        "\n{} busy ratio : {:.2?} | CI {:.4?}",
+        bracketed(model.topology.workstations.iter().map(|ws| &ws.name)),
+        mean.utilization,
+        half_width.utilization
+    );
+    log!(
+        "\n{} throughput : {:.2?} | CI {:.4?} ",
+        bracketed(model.topology.products.iter().map(|p| &p.name)),
+        mean.throughput,
+        half_width.throughput
+    );
+    log!(
+        "\n{} blocked ratio: {:.4?} | CI {:.4?}",
+        bracketed(model.topology.inspectors.iter().map(|i| &i.name)),
+        mean.blocking,
+        half_width.blocking
+    );
+    log!("\n Total Average Occupancy: {:.4?}", mean.wip);
+
+    print_controlled(&model.topology, &cis, &controlled);
+}
@@ -0,0 +1,138 @@
+use crate::statistics::{self, ConfidenceInterval};
+
+// control variates: a measure y of each replication is corrected by
+// controls c whose expectations mu are known, with
+//   y - beta . (mean(c) - mu)
+// where beta, the coefficients minimising the variance, is the least
+// squares regression of y on c. estimating beta costs a degree of
+// freedom per control, after Lavenberg and Welch.
+
+pub fn control_variates(
+    y: &[f64],
+    controls: &[Vec<f64>],
+    means: &[f64],
+    confidence: f64,
+) -> Option<ConfidenceInterval> {
+    // controls holds the controls of each replication. None if there
+    // are too few replications to estimate the coefficients
+    let n = y.len();
+    let y_bar = statistics::mean(y);
+    let c_bar = (0..means.len())
+        .map(|j| statistics::mean(&controls.iter().map(|c| c[j]).collect::<Vec<f64>>()))
+        .collect::<Vec<f64>>();
+
+    // a control with no variance, i.e a deterministic
+    // service time, carries no information so is left out
+    let used = (0..means.len())
+        .filter(|j| controls.iter().any(|c| c[*j] != c_bar[*j]))
+        .collect::<Vec<usize>>();
+    let q = used.len();
+    if n < q + 3 {
+        return None;
+    }
+
+    // the centred cross products of the controls with
+    // each other and with y, and the control offsets
+    let mut s_cc = vec![vec![0.0; q]; q];
+    let mut s_cy = vec![0.0; q];
+    for (c, yi) in controls.iter().zip(y) {
+        for (a, &j) in used.iter().enumerate() {
+            for (b, &k) in used.iter().enumerate() {
+                s_cc[a][b] += (c[j] - c_bar[j]) * (c[k] - c_bar[k]);
+            }
+            s_cy[a] += (c[j] - c_bar[j]) * (yi - y_bar);
+        }
+    }
+    let offset = used
+        .iter()
+        .map(|&j| c_bar[j] - means[j])
+        .collect::<Vec<f64>>();
+
+    let beta = solve(s_cc.clone(), s_cy)?;
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+    let residual_variance = controls
+        .iter()
+        .zip(y)
+        .map(|(c, yi)| {
+            let centred = used.iter().map(|&j| c[j] - c_bar[j]).collect::<Vec<f64>>();
+            (yi - y_bar - dot(&beta, &centred)).powi(2)
+        })
+        .sum::<f64>()
+        / (n - q - 1) as f64;
+    // the variance of the estimate grows with how far the
+    // controls of this sample landed from their means
+    let variance =
+        residual_variance * (1.0 / n as f64 + dot(&offset, &solve(s_cc, offset.clone())?));
+    Some(ConfidenceInterval::new(
+        y_bar - dot(&beta, &offset),
+        variance.sqrt(),
+        (n - q - 1) as f64,
+        confidence,
+    ))
+}
+
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    // gaussian elimination with partial pivoting,
+    // None if the matrix is (numerically) singular
+    let n = b.len();
+    let scale = a.iter().flatten().fold(0.0_f64, |acc, x| acc.max(x.abs()));
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|i, j| a[*i][col].abs().partial_cmp(&a[*j][col].abs()).unwrap())?;
+        if a[pivot][col].abs() <= 1e-12 * scale {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_row[col];
+            for (x, p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest = (row + 1..n).map(|k| a[row][k] * x[k]).sum::<f64>();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_an_exact_linear_relation() {
+        // y = 1 + 2 c1 - 0.5 c2 exactly, and a constant third control
+        // is left out, so the corrected estimate is 1 + 2 mu1 - 0.5 mu2
+        // with no residual variance at all
+        let controls = [
+            [1.0, 4.0],
+            [2.0, 1.0],
+            [3.0, 5.0],
+            [4.0, 2.0],
+            [5.0, 7.0],
+            [6.0, 3.0],
+        ]
+        .iter()
+        .map(|c| vec![c[0], c[1], 7.0])
+        .collect::<Vec<Vec<f64>>>();
+        let y = controls
+            .iter()
+            .map(|c| 1.0 + 2.0 * c[0] - 0.5 * c[1])
+            .collect::<Vec<f64>>();
+        let interval = control_variates(&y, &controls, &[3.0, 4.0, 7.0], 0.95).unwrap();
+        assert!((interval.mean - 5.0).abs() < 1e-12, "{}", interval.mean);
+        assert!(interval.half_width < 1e-6, "{}", interval.half_width);
+    }
+
+    #[test]
+    fn needs_more_replications_than_controls() {
+        let controls = vec![vec![1.0], vec![2.0], vec![4.0]];
+        assert!(control_variates(&[1.0, 2.0, 3.0], &controls, &[2.0], 0.95).is_none());
+    }
+}
@@ -0,0 +1,556 @@
+use std::fmt::{Display, Formatter, Result};
+use std::path::Path;
+
+use crate::fitting::{self, Family, Fit};
+use crate::random::Random;
+use crate::simulation::Duration;
+use crate::special::{ln_gamma, normal_cdf, normal_quantile};
+use crate::trace;
+
+// a source of service times. every workstation and
+// inspector stream draws from its own distribution
+pub trait Distribution: Display {
+    fn sample(&self, rand: &mut Random) -> Duration;
+    // the expected service time in minutes
+    fn mean(&self) -> f64;
+}
+
+fn open_unit(rand: &mut Random) -> f64 {
+    // a uniform on (0, 1) for inverse cdfs which are infinite at 0 or 1
+    rand.float().clamp(f64::EPSILON, 1.0 - f64::EPSILON)
+}
+
+pub struct Exponential {
+    pub rate: f64,
+}
+
+impl Distribution for Exponential {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        Duration::of_minutes(-(1.0 - rand.float()).ln() / self.rate)
+    }
+
+    fn mean(&self) -> f64 {
+        1.0 / self.rate
+    }
+}
+
+pub struct Uniform {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Distribution for Uniform {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        Duration::of_minutes(self.low + (self.high - self.low) * rand.float())
+    }
+
+    fn mean(&self) -> f64 {
+        (self.low + self.high) / 2.0
+    }
+}
+
+pub struct Triangular {
+    pub low: f64,
+    pub mode: f64,
+    pub high: f64,
+}
+
+impl Distribution for Triangular {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        let u = rand.float();
+        let (a, c, b) = (self.low, self.mode, self.high);
+        let split = (c - a) / (b - a);
+        Duration::of_minutes(match u < split {
+            true => a + (u * (b - a) * (c - a)).sqrt(),
+            false => b - ((1.0 - u) * (b - a) * (b - c)).sqrt(),
+        })
+    }
+
+    fn mean(&self) -> f64 {
+        (self.low + self.mode + self.high) / 3.0
+    }
+}
+
+// a normal distribution truncated at zero, since
+// a service time can never be negative
+pub struct Normal {
+    pub mean: f64,
+    pub sd: f64,
+}
+
+impl Distribution for Normal {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        // inverse transform restricted to the part of the cdf above zero
+        let below_zero = normal_cdf(-self.mean / self.sd);
+        let u = below_zero + open_unit(rand) * (1.0 - below_zero);
+        let u = u.clamp(f64::EPSILON, 1.0 - f64::EPSILON);
+        Duration::of_minutes((self.mean + self.sd * normal_quantile(u)).max(0.0))
+    }
+
+    fn mean(&self) -> f64 {
+        // of the truncated distribution, mu + sd * pdf(a) / (1 - cdf(a))
+        let a = -self.mean / self.sd;
+        let pdf = (-0.5 * a * a).exp() / (2.0 * std::f64::consts::PI).sqrt();
+        self.mean + self.sd * pdf / (1.0 - normal_cdf(a))
+    }
+}
+
+pub struct Lognormal {
+    pub log_mean: f64,
+    pub log_sd: f64,
+}
+
+impl Distribution for Lognormal {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        Duration::of_minutes((self.log_mean + self.log_sd * normal_quantile(open_unit(rand))).exp())
+    }
+
+    fn mean(&self) -> f64 {
+        (self.log_mean + self.log_sd * self.log_sd / 2.0).exp()
+    }
+}
+
+pub struct Weibull {
+    pub shape: f64,
+    pub scale: f64,
+}
+
+impl Distribution for Weibull {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        Duration::of_minutes(self.scale * (-(1.0 - rand.float()).ln()).powf(1.0 / self.shape))
+    }
+
+    fn mean(&self) -> f64 {
+        self.scale * ln_gamma(1.0 + 1.0 / self.shape).exp()
+    }
+}
+
+pub struct Gamma {
+    pub shape: f64,
+    pub scale: f64,
+}
+
+impl Gamma {
+    fn standard(shape: f64, rand: &mut Random) -> f64 {
+        // Marsaglia and Tsang's method, boosted for shapes below 1
+        if shape < 1.0 {
+            return Self::standard(shape + 1.0, rand) * open_unit(rand).powf(1.0 / shape);
+        }
+        let d = shape - 1.0 / 3.0;
+        let c = 1.0 / (9.0 * d).sqrt();
+        loop {
+            let z = normal_quantile(open_unit(rand));
+            let v = (1.0 + c * z).powi(3);
+            if v <= 0.0 {
+                continue;
+            }
+            let u = open_unit(rand);
+            if u.ln() < 0.5 * z * z + d - d * v + d * v.ln() {
+                return d * v;
+            }
+        }
+    }
+}
+
+impl Distribution for Gamma {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        Duration::of_minutes(self.scale * Self::standard(self.shape, rand))
+    }
+
+    fn mean(&self) -> f64 {
+        self.shape * self.scale
+    }
+}
+
+// the sum of k exponential stages with the same rate
+pub struct Erlang {
+    pub k: usize,
+    pub rate: f64,
+}
+
+impl Distribution for Erlang {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        // sum the logs rather than taking the log of the product, which
+        // underflows to 0 after a few hundred stages
+        let sum: f64 = (0..self.k).map(|_| open_unit(rand).ln()).sum();
+        Duration::of_minutes(-sum / self.rate)
+    }
+
+    fn mean(&self) -> f64 {
+        self.k as f64 / self.rate
+    }
+}
+
+pub struct Deterministic {
+    pub value: f64,
+}
+
+impl Distribution for Deterministic {
+    fn sample(&self, _: &mut Random) -> Duration {
+        Duration::of_minutes(self.value)
+    }
+
+    fn mean(&self) -> f64 {
+        self.value
+    }
+}
+
+// the piecewise linear interpolation of the empirical cdf
+// of observed data, which never produces values outside
+// the observed range
+pub struct Empirical {
+    sorted: Vec<f64>,
+}
+
+impl Empirical {
+    pub fn new(data: &[f64]) -> Self {
+        let mut sorted = data.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        Empirical { sorted }
+    }
+}
+
+impl Distribution for Empirical {
+    fn sample(&self, rand: &mut Random) -> Duration {
+        let n = self.sorted.len();
+        if n == 1 {
+            return Duration::of_minutes(self.sorted[0]);
+        }
+        let p = rand.float() * (n - 1) as f64;
+        let i = (p.floor() as usize).min(n - 2);
+        Duration::of_minutes(
+            self.sorted[i] + (p - i as f64) * (self.sorted[i + 1] - self.sorted[i]),
+        )
+    }
+
+    fn mean(&self) -> f64 {
+        // every segment between neighbouring points is equally
+        // likely and contributes the midpoint of its ends
+        let n = self.sorted.len();
+        match n == 1 {
+            true => self.sorted[0],
+            false => {
+                self.sorted
+                    .windows(2)
+                    .map(|w| (w[0] + w[1]) / 2.0)
+                    .sum::<f64>()
+                    / (n - 1) as f64
+            }
+        }
+    }
+}
+
+impl Display for Exponential {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "exponential({:.5})", self.rate)
+    }
+}
+
+impl Display for Uniform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "uniform({}, {})", self.low, self.high)
+    }
+}
+
+impl Display for Triangular {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "triangular({}, {}, {})", self.low, self.mode, self.high)
+    }
+}
+
+impl Display for Normal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "normal({:.5}, {:.5})", self.mean, self.sd)
+    }
+}
+
+impl Display for Lognormal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "lognormal({:.5}, {:.5})", self.log_mean, self.log_sd)
+    }
+}
+
+impl Display for Weibull {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "weibull({:.5}, {:.5})", self.shape, self.scale)
+    }
+}
+
+impl Display for Gamma {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "gamma({:.5}, {:.5})", self.shape, self.scale)
+    }
+}
+
+impl Display for Erlang {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "erlang({}, {:.5})", self.k, self.rate)
+    }
+}
+
+impl Display for Deterministic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "deterministic({})", self.value)
+    }
+}
+
+impl Display for Empirical {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "empirical({} points)", self.sorted.len())
+    }
+}
+
+pub fn from_fit(fit: &Fit) -> Box<dyn Distribution> {
+    let p = |i: usize| fit.params[i].value;
+    match fit.family {
+        Family::Exponential => Box::new(Exponential { rate: p(0) }),
+        Family::Weibull => Box::new(Weibull {
+            shape: p(0),
+            scale: p(1),
+        }),
+        Family::Lognormal => Box::new(Lognormal {
+            log_mean: p(0),
+            log_sd: p(1),
+        }),
+        Family::Gamma => Box::new(Gamma {
+            shape: p(0),
+            scale: p(1),
+        }),
+        Family::Normal => Box::new(Normal {
+            mean: p(0),
+            sd: p(1),
+        }),
+    }
+}
+
+pub fn parse(spec: &str, data: &[f64]) -> std::result::Result<Box<dyn Distribution>, String> {
+    // parses a distribution such as "triangular(1, 2, 5)".
+    // a name without parameters, i.e "gamma", is fitted to data
+    // by maximum likelihood, and "empirical" replays data's empirical
+    // cdf. "empirical(path)" reads the data from a trace file instead
+    let spec = spec.trim();
+    let (name, args) = match spec.find('(') {
+        Some(open) if spec.ends_with(')') => (&spec[..open], Some(&spec[open + 1..spec.len() - 1])),
+        Some(_) => return Err(format!("missing ')' in {spec:?}")),
+        None => (spec, None),
+    };
+    let name = name.trim().to_lowercase();
+    if args.is_none() && data.is_empty() {
+        return Err(format!(
+            "{name:?} needs observed times to be fitted to, give its parameters"
+        ));
+    }
+
+    if name == "empirical" {
+        return match args {
+            None => Ok(Box::new(Empirical::new(data))),
+            Some(path) => trace::read_trace(Path::new(path.trim()))
+                .map(|durations| Box::new(Empirical::new(&trace::as_minutes(&durations))) as _)
+                .map_err(|e| e.to_string()),
+        };
+    }
+
+    let args = match args {
+        Some(args) => args,
+        None => {
+            let family = Family::ALL
+                .iter()
+                .find(|f| f.name().to_lowercase() == name)
+                .ok_or(format!(
+                    "{name:?} cannot be fitted to data, give its parameters"
+                ))?;
+            return fitting::fit(*family, data)
+                .map(|fit| from_fit(&fit))
+                .ok_or(format!("could not fit a {name} distribution to the data"));
+        }
+    };
+
+    let params = args
+        .split(',')
+        .map(|a| a.trim().parse::<f64>())
+        .collect::<std::result::Result<Vec<f64>, _>>()
+        .map_err(|e| format!("bad parameter in {spec:?}: {e}"))?;
+    // i.e nan or inf, which parse but no family can take
+    if let Some(p) = params.iter().find(|p| !p.is_finite()) {
+        return Err(format!("parameters must be finite, got {p} in {spec:?}"));
+    }
+    let expect = |count: usize| match params.len() == count {
+        true => Ok(()),
+        false => Err(format!(
+            "{name} takes {count} parameter(s), got {}",
+            params.len()
+        )),
+    };
+    let positive = |v: f64, what: &str| match v > 0.0 {
+        true => Ok(v),
+        false => Err(format!("{name} {what} must be positive, got {v}")),
+    };
+
+    let distribution: Box<dyn Distribution> = match name.as_str() {
+        "exponential" => {
+            expect(1)?;
+            Box::new(Exponential {
+                rate: positive(params[0], "rate")?,
+            })
+        }
+        "uniform" => {
+            expect(2)?;
+            if !(0.0 <= params[0] && params[0] <= params[1]) {
+                return Err(format!("uniform needs 0 <= low <= high in {spec:?}"));
+            }
+            Box::new(Uniform {
+                low: params[0],
+                high: params[1],
+            })
+        }
+        "triangular" => {
+            expect(3)?;
+            if !(0.0 <= params[0] && params[0] <= params[1] && params[1] <= params[2])
+                || params[0] == params[2]
+            {
+                return Err(format!(
+                    "triangular needs 0 <= low <= mode <= high in {spec:?}"
+                ));
+            }
+            Box::new(Triangular {
+                low: params[0],
+                mode: params[1],
+                high: params[2],
+            })
+        }
+        "normal" => {
+            expect(2)?;
+            Box::new(Normal {
+                mean: params[0],
+                sd: positive(params[1], "sd")?,
+            })
+        }
+        "lognormal" => {
+            expect(2)?;
+            Box::new(Lognormal {
+                log_mean: params[0],
+                log_sd: positive(params[1], "log-sd")?,
+            })
+        }
+        "weibull" => {
+            expect(2)?;
+            Box::new(Weibull {
+                shape: positive(params[0], "shape")?,
+                scale: positive(params[1], "scale")?,
+            })
+        }
+        "gamma" => {
+            expect(2)?;
+            Box::new(Gamma {
+                shape: positive(params[0], "shape")?,
+                scale: positive(params[1], "scale")?,
+            })
+        }
+        "erlang" => {
+            expect(2)?;
+            if params[0] < 1.0 || params[0].fract() != 0.0 {
+                return Err(format!("erlang needs a whole number of stages in {spec:?}"));
+            }
+            Box::new(Erlang {
+                k: params[0] as usize,
+                rate: positive(params[1], "rate")?,
+            })
+        }
+        "deterministic" | "constant" => {
+            expect(1)?;
+            if params[0] < 0.0 {
+                return Err(format!("a service time cannot be negative in {spec:?}"));
+            }
+            Box::new(Deterministic { value: params[0] })
+        }
+        _ => return Err(format!("unknown distribution {name:?}")),
+    };
+    Ok(distribution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Generator;
+
+    // the error parsing a spec without data
+    fn error(spec: &str) -> String {
+        match parse(spec, &[]) {
+            Ok(_) => panic!("{spec:?} parsed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn rejects_parameters_which_are_not_finite() {
+        for spec in [
+            "deterministic(nan)",
+            "deterministic(inf)",
+            "uniform(0, inf)",
+            "triangular(1, 2, inf)",
+            "normal(nan, 1)",
+            "lognormal(-inf, 1)",
+        ] {
+            assert!(error(spec).contains("finite"), "{spec}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_specs() {
+        assert_eq!(
+            error("exponential(1, 2)"),
+            "exponential takes 1 parameter(s), got 2"
+        );
+        assert_eq!(error("gamma(2, 3"), "missing ')' in \"gamma(2, 3\"");
+        assert_eq!(error("cauchy(0, 1)"), "unknown distribution \"cauchy\"");
+        assert_eq!(
+            error("gamma"),
+            "\"gamma\" needs observed times to be fitted to, give its parameters"
+        );
+        assert!(error("uniform(5, 2)").contains("low <= high"));
+        assert!(error("erlang(2.5, 1)").contains("whole number of stages"));
+        assert!(error("weibull(0, 1)").contains("shape must be positive"));
+    }
+
+    // the mean of n samples from the first stream of a fixed seed
+    fn sample_mean(distribution: &dyn Distribution, n: usize) -> f64 {
+        let mut rand = Random::streams(Generator::Lcg, 1, 1).remove(0);
+        (0..n)
+            .map(|_| distribution.sample(&mut rand).as_minutes())
+            .sum::<f64>()
+            / n as f64
+    }
+
+    #[test]
+    fn samples_average_to_the_mean() {
+        let data = [1.0, 2.0, 4.0, 8.0];
+        for spec in [
+            "exponential(0.5)",
+            "uniform(2, 6)",
+            "triangular(1, 4, 9)",
+            "normal(1, 2)",
+            "lognormal(0.5, 0.4)",
+            "weibull(1.5, 3)",
+            "gamma(2.5, 1.5)",
+            "gamma(0.5, 2)",
+            "erlang(3, 0.5)",
+            "deterministic(4)",
+            "empirical",
+        ] {
+            let distribution = parse(spec, &data).unwrap_or_else(|e| panic!("{e}"));
+            let (mean, sampled) = (distribution.mean(), sample_mean(&*distribution, 200_000));
+            assert!(
+                (sampled - mean).abs() < 0.01 * mean,
+                "{spec}: {sampled} against {mean}"
+            );
+        }
+    }
+
+    #[test]
+    fn erlang_with_many_stages_does_not_underflow() {
+        // the product of 2000 uniforms is far below the smallest double
+        let erlang = Erlang { k: 2000, rate: 1.0 };
+        let sampled = sample_mean(&erlang, 100);
+        assert!((sampled - 2000.0).abs() < 20.0, "{sampled}");
+    }
+}
@@ -1,15 +1,18 @@
-use crate::Component;
+use std::fmt::{Display, Formatter, Result};
+
+use crate::component::Component;
 //use crate::Duration;
 use crate::workstation::Type as WS;
 use crate::Product;
 use crate::TimeStamp as TS;
 
+#[allow(clippy::large_enum_variant)]
 pub enum EnqueueResult {
-    CouldEnqueue(bool, Component, WS, TS, bool),
+    CouldEnqueue(Component, WS, TS, bool),
     Fail,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum FacilityEvent {
     // a product was assembled at a workstation
     Assembled(Product, WS),
@@ -20,7 +23,28 @@ pub enum FacilityEvent {
     SimulationStarted,
 }
 
+// what an event is about, so that it only
+// goes to the actors which listen for it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Topic {
+    // a product was assembled at the workstation of this index
+    Assembled(usize),
+
+    // the workstation of this index was told to start
+    WorkstationStarted(usize),
+
+    SimulationStarted,
+}
+
 impl FacilityEvent {
+    pub fn topic(&self) -> Topic {
+        match self {
+            FacilityEvent::Assembled(_, ws) => Topic::Assembled(ws.index()),
+            FacilityEvent::WorkstationStarted(ws, _) => Topic::WorkstationStarted(ws.index()),
+            FacilityEvent::SimulationStarted => Topic::SimulationStarted,
+        }
+    }
+
     pub fn timestamp(&self) -> TS {
         match self {
             FacilityEvent::Assembled(product, _) => product.timestamp(),
@@ -29,3 +53,15 @@ impl FacilityEvent {
         }
     }
 }
+
+impl Display for FacilityEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            FacilityEvent::Assembled(product, ws) => {
+                write!(f, "{} assembled by {}", product.name(), ws.name())
+            }
+            FacilityEvent::WorkstationStarted(ws, _) => write!(f, "{} started", ws.name()),
+            FacilityEvent::SimulationStarted => write!(f, "simulation started"),
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use crate::random::{splitmix64, Generator, RandomSource};
+
+// generators with published reference implementations, which the
+// tests check them against
+
+// L'Ecuyer's MRG32k3a, the generator behind RngStream. the period
+// of ~2^191 is cut into streams of 2^127 and substreams of 2^76
+#[derive(Clone)]
+pub struct Mrg32k3a {
+    // the last three values of each of the two recurrences
+    s: [u64; 6],
+}
+
+type Matrix = [[u64; 3]; 3];
+
+impl Mrg32k3a {
+    const M1: u64 = 4294967087;
+    const M2: u64 = 4294944443;
+    const A12: u64 = 1403580;
+    const A13N: u64 = 810728;
+    const A21: u64 = 527612;
+    const A23N: u64 = 1370589;
+    const NORM: f64 = 2.328_306_549_295_728e-10;
+
+    // the transition matrices of the two recurrences to the power 2^76 and 2^127
+    const A1P76: Matrix = [
+        [82758667, 1871391091, 4127413238],
+        [3672831523, 69195019, 1871391091],
+        [3672091415, 3528743235, 69195019],
+    ];
+    const A2P76: Matrix = [
+        [1511326704, 3759209742, 1610795712],
+        [4292754251, 1511326704, 3889917532],
+        [3859662829, 4292754251, 3708466080],
+    ];
+    const A1P127: Matrix = [
+        [2427906178, 3580155704, 949770784],
+        [226153695, 1230515664, 3580155704],
+        [1988835001, 986791581, 1230515664],
+    ];
+    const A2P127: Matrix = [
+        [1464411153, 277697599, 1610723613],
+        [32183930, 1464411153, 1022607788],
+        [2824425944, 32183930, 2093834863],
+    ];
+
+    pub fn with_seed(seed: u32) -> Self {
+        // neither half of the state may be all zero, which
+        // splitmix64 makes vanishingly unlikely
+        let mut state = seed as u64;
+        let mut s = [0; 6];
+        for (i, word) in s.iter_mut().enumerate() {
+            let m = match i < 3 {
+                true => Self::M1,
+                false => Self::M2,
+            };
+            *word = splitmix64(&mut state) % m;
+        }
+        assert!(s[..3].iter().any(|w| *w != 0) && s[3..].iter().any(|w| *w != 0));
+        Mrg32k3a { s }
+    }
+
+    pub fn from_state(state: &[u64]) -> Option<Self> {
+        let s: [u64; 6] = state.try_into().ok()?;
+        let valid =
+            |words: &[u64], m: u64| words.iter().all(|w| *w < m) && words.iter().any(|w| *w != 0);
+        match valid(&s[..3], Self::M1) && valid(&s[3..], Self::M2) {
+            true => Some(Mrg32k3a { s }),
+            false => None,
+        }
+    }
+
+    fn next_u01(&mut self) -> f64 {
+        // a12 * s1 - a13n * s0 fits easily in an i64
+        let s = &mut self.s;
+        let recurrence = |a: u64, x: u64, b: u64, y: u64, m: u64| {
+            (a as i64 * x as i64 - b as i64 * y as i64).rem_euclid(m as i64) as u64
+        };
+        let p1 = recurrence(Self::A12, s[1], Self::A13N, s[0], Self::M1);
+        s[0] = s[1];
+        s[1] = s[2];
+        s[2] = p1;
+        let p2 = recurrence(Self::A21, s[5], Self::A23N, s[3], Self::M2);
+        s[3] = s[4];
+        s[4] = s[5];
+        s[5] = p2;
+        match p1 > p2 {
+            true => (p1 - p2) as f64 * Self::NORM,
+            false => (p1 + Self::M1 - p2) as f64 * Self::NORM,
+        }
+    }
+
+    fn jump(&mut self, a1: &Matrix, a2: &Matrix) {
+        let (x1, x2) = (
+            [self.s[0], self.s[1], self.s[2]],
+            [self.s[3], self.s[4], self.s[5]],
+        );
+        let (y1, y2) = (mat_vec(a1, &x1, Self::M1), mat_vec(a2, &x2, Self::M2));
+        self.s = [y1[0], y1[1], y1[2], y2[0], y2[1], y2[2]];
+    }
+}
+
+fn mat_vec(a: &Matrix, x: &[u64; 3], m: u64) -> [u64; 3] {
+    let m = m as u128;
+    a.map(|row| {
+        (row.iter()
+            .zip(x.iter())
+            .map(|(a, x)| *a as u128 * *x as u128 % m)
+            .sum::<u128>()
+            % m) as u64
+    })
+}
+
+impl RandomSource for Mrg32k3a {
+    fn float(&mut self) -> f64 {
+        self.next_u01()
+    }
+
+    fn jump_substream(&mut self) {
+        self.jump(&Self::A1P76, &Self::A2P76);
+    }
+
+    fn jump_stream(&mut self) {
+        self.jump(&Self::A1P127, &Self::A2P127);
+    }
+
+    fn boxed_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+
+    fn generator(&self) -> Generator {
+        Generator::Mrg32k3a
+    }
+
+    fn state(&self) -> Vec<u64> {
+        self.s.to_vec()
+    }
+}
+
+// O'Neill's PCG64 (XSL RR 128/64), an lcg on 128 bits with a permuted
+// output. jumps use the lcg's closed form: streams of 2^96, substreams of 2^64
+#[derive(Clone)]
+pub struct Pcg64 {
+    state: u128,
+    increment: u128,
+}
+
+impl Pcg64 {
+    const MULTIPLIER: u128 = 0x2360ed051fc65da44385df649fccf645;
+
+    fn new(init_state: u128, init_sequence: u128) -> Self {
+        // the seeding of the reference pcg64_srandom_r
+        let mut gen = Pcg64 {
+            state: 0,
+            increment: (init_sequence << 1) | 1,
+        };
+        gen.step();
+        gen.state = gen.state.wrapping_add(init_state);
+        gen.step();
+        gen
+    }
+
+    pub fn with_seed(seed: u32) -> Self {
+        let mut state = seed as u64;
+        let mut word = || splitmix64(&mut state) as u128;
+        let init_state = (word() << 64) | word();
+        let init_sequence = (word() << 64) | word();
+        Pcg64::new(init_state, init_sequence)
+    }
+
+    pub fn from_state(state: &[u64]) -> Option<Self> {
+        // the state and increment, high word first. the increment is always odd
+        match *state {
+            [s_high, s_low, i_high, i_low] if i_low & 1 == 1 => Some(Pcg64 {
+                state: ((s_high as u128) << 64) | s_low as u128,
+                increment: ((i_high as u128) << 64) | i_low as u128,
+            }),
+            _ => None,
+        }
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.step();
+        let xored = ((self.state >> 64) as u64) ^ (self.state as u64);
+        xored.rotate_right((self.state >> 122) as u32)
+    }
+
+    fn advance(&mut self, mut steps: u128) {
+        // the same squaring as LcmGenerator::advance, modulo 2^128
+        let (mut a, mut c) = (Self::MULTIPLIER, self.increment);
+        let (mut mul, mut add) = (1u128, 0u128);
+        while steps > 0 {
+            if steps & 1 == 1 {
+                mul = mul.wrapping_mul(a);
+                add = add.wrapping_mul(a).wrapping_add(c);
+            }
+            c = c.wrapping_mul(a.wrapping_add(1));
+            a = a.wrapping_mul(a);
+            steps >>= 1;
+        }
+        self.state = self.state.wrapping_mul(mul).wrapping_add(add);
+    }
+}
+
+impl RandomSource for Pcg64 {
+    fn float(&mut self) -> f64 {
+        unit_float(self.next_u64())
+    }
+
+    fn jump_substream(&mut self) {
+        self.advance(1 << 64);
+    }
+
+    fn jump_stream(&mut self) {
+        self.advance(1 << 96);
+    }
+
+    fn boxed_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+
+    fn generator(&self) -> Generator {
+        Generator::Pcg64
+    }
+
+    fn state(&self) -> Vec<u64> {
+        vec![
+            (self.state >> 64) as u64,
+            self.state as u64,
+            (self.increment >> 64) as u64,
+            self.increment as u64,
+        ]
+    }
+}
+
+// Blackman and Vigna's xoshiro256**. its jump polynomials give
+// substreams of 2^128 and streams of 2^192
+#[derive(Clone)]
+pub struct Xoshiro256StarStar {
+    s: [u64; 4],
+}
+
+impl Xoshiro256StarStar {
+    const JUMP: [u64; 4] = [
+        0x180ec6d33cfd0aba,
+        0xd5a61266f0c9392c,
+        0xa9582618e03fc9aa,
+        0x39abdc4529b1661c,
+    ];
+    const LONG_JUMP: [u64; 4] = [
+        0x76e15d3efefdcbbf,
+        0xc5004e441c522fb3,
+        0x77710069854ee241,
+        0x39109bb02acbe635,
+    ];
+
+    pub fn with_seed(seed: u32) -> Self {
+        // seeded with splitmix64 as its authors recommend
+        let mut state = seed as u64;
+        let s = [0; 4].map(|_| splitmix64(&mut state));
+        assert!(s.iter().any(|w| *w != 0));
+        Xoshiro256StarStar { s }
+    }
+
+    pub fn from_state(state: &[u64]) -> Option<Self> {
+        let s: [u64; 4] = state.try_into().ok()?;
+        match s.iter().any(|w| *w != 0) {
+            true => Some(Xoshiro256StarStar { s }),
+            false => None,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn jump(&mut self, polynomial: &[u64; 4]) {
+        let mut jumped = [0; 4];
+        for word in polynomial {
+            for bit in 0..64 {
+                if word & (1 << bit) != 0 {
+                    for (j, s) in jumped.iter_mut().zip(self.s.iter()) {
+                        *j ^= s;
+                    }
+                }
+                self.next_u64();
+            }
+        }
+        self.s = jumped;
+    }
+}
+
+impl RandomSource for Xoshiro256StarStar {
+    fn float(&mut self) -> f64 {
+        unit_float(self.next_u64())
+    }
+
+    fn jump_substream(&mut self) {
+        self.jump(&Self::JUMP);
+    }
+
+    fn jump_stream(&mut self) {
+        self.jump(&Self::LONG_JUMP);
+    }
+
+    fn boxed_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+
+    fn generator(&self) -> Generator {
+        Generator::Xoshiro256StarStar
+    }
+
+    fn state(&self) -> Vec<u64> {
+        self.s.to_vec()
+    }
+}
+
+fn unit_float(x: u64) -> f64 {
+    // the top 53 bits as a float on [0, 1)
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mrg32k3a_matches_rngstream() {
+        // the first outputs from RngStream's default seed of 12345
+        let mut gen = Mrg32k3a { s: [12345; 6] };
+        for e in [0.127011122046577, 0.318527565396794, 0.309186015583270] {
+            assert!((gen.next_u01() - e).abs() < 1e-14);
+        }
+    }
+
+    fn mat_mul(a: &Matrix, b: &Matrix, m: u64) -> Matrix {
+        let column = |j: usize| [b[0][j], b[1][j], b[2][j]];
+        let columns = [column(0), column(1), column(2)].map(|c| mat_vec(a, &c, m));
+        [0, 1, 2].map(|i| [columns[0][i], columns[1][i], columns[2][i]])
+    }
+
+    #[test]
+    fn mrg32k3a_jump_matrices() {
+        // against squarings of the recurrences
+        let a1 = [
+            [0, 1, 0],
+            [0, 0, 1],
+            [Mrg32k3a::M1 - Mrg32k3a::A13N, Mrg32k3a::A12, 0],
+        ];
+        let a2 = [
+            [0, 1, 0],
+            [0, 0, 1],
+            [Mrg32k3a::M2 - Mrg32k3a::A23N, 0, Mrg32k3a::A21],
+        ];
+        let power = |a: &Matrix, e: usize, m: u64| (0..e).fold(*a, |p, _| mat_mul(&p, &p, m));
+        assert_eq!(power(&a1, 76, Mrg32k3a::M1), Mrg32k3a::A1P76);
+        assert_eq!(power(&a2, 76, Mrg32k3a::M2), Mrg32k3a::A2P76);
+        assert_eq!(power(&a1, 127, Mrg32k3a::M1), Mrg32k3a::A1P127);
+        assert_eq!(power(&a2, 127, Mrg32k3a::M2), Mrg32k3a::A2P127);
+    }
+
+    #[test]
+    fn pcg64_matches_reference() {
+        // the first outputs of pcg64 seeded with (42, 54) in
+        // the reference implementation's check-pcg64 program
+        let mut gen = Pcg64::new(42, 54);
+        let expected: [u64; 6] = [
+            0x86b1da1d72062b68,
+            0x1304aa46c9853d39,
+            0xa3670e9e0dd50358,
+            0xf9090e529a7dae00,
+            0xc85b9fd837996f2c,
+            0x606121f8e3919196,
+        ];
+        let mut jumped = gen.clone();
+        jumped.advance(expected.len() as u128);
+        for e in expected {
+            assert_eq!(gen.next_u64(), e);
+        }
+        // jumping ahead agrees with stepping
+        assert_eq!(jumped.state, gen.state);
+    }
+
+    #[test]
+    fn xoshiro256starstar_matches_reference() {
+        // the reference implementation seeded with the state 1, 2, 3, 4
+        let mut gen = Xoshiro256StarStar { s: [1, 2, 3, 4] };
+        let expected: [u64; 6] = [
+            11520,
+            0,
+            1509978240,
+            1215971899390074240,
+            1216172134540287360,
+            607988272756665600,
+        ];
+        for e in expected {
+            assert_eq!(gen.next_u64(), e);
+        }
+    }
+}
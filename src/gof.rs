@@ -0,0 +1,145 @@
+use crate::fitting::Fit;
+use crate::special::{chi_square_survival, kolmogorov_survival};
+
+// goodness-of-fit tests of the observed service times
+// against a fitted distribution. since the parameters are
+// estimated from the same data the K-S p-values are conservative
+// (the test accepts slightly too often), while the chi-square
+// test accounts for it by dropping a degree of freedom per parameter.
+
+#[derive(Copy, Clone, Debug)]
+pub struct TestResult {
+    pub statistic: f64,
+    pub p_value: f64,
+}
+
+impl TestResult {
+    pub fn accepts(&self, alpha: f64) -> bool {
+        self.p_value >= alpha
+    }
+}
+
+pub fn equiprobable_bins(n: usize) -> usize {
+    // about sqrt(n) bins keeps the expected count per bin
+    // at sqrt(n) >= 5 for any sample big enough to test
+    ((n as f64).sqrt().floor() as usize).max(3)
+}
+
+pub fn degrees_of_freedom(fit: &Fit, bins: usize) -> usize {
+    bins.saturating_sub(1 + fit.params.len()).max(1)
+}
+
+pub fn chi_square(data: &[f64], fit: &Fit, bins: usize) -> TestResult {
+    // with bins that are equiprobable under the fitted distribution,
+    // F(x) is uniform on [0, 1] so each bin is an equal slice of it
+    let mut observed = vec![0usize; bins];
+    for x in data {
+        let bin = (fit.cdf(*x) * bins as f64).floor() as usize;
+        observed[bin.min(bins - 1)] += 1;
+    }
+    let expected = data.len() as f64 / bins as f64;
+    let statistic = observed
+        .iter()
+        .map(|o| (*o as f64 - expected).powi(2) / expected)
+        .sum::<f64>();
+    TestResult {
+        statistic,
+        p_value: chi_square_survival(statistic, degrees_of_freedom(fit, bins)),
+    }
+}
+
+pub fn kolmogorov_smirnov(data: &[f64], fit: &Fit) -> TestResult {
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let n = sorted.len() as f64;
+
+    let statistic = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            let f = fit.cdf(*x);
+            f64::max(f - i as f64 / n, (i + 1) as f64 / n - f)
+        })
+        .fold(0.0, f64::max);
+
+    // Stephens' small sample correction to the limiting distribution
+    let lambda = (n.sqrt() + 0.12 + 0.11 / n.sqrt()) * statistic;
+    TestResult {
+        statistic,
+        p_value: kolmogorov_survival(lambda),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitting::{Family, Parameter};
+
+    fn unit_exponential() -> Fit {
+        Fit {
+            family: Family::Exponential,
+            params: vec![Parameter {
+                name: "rate",
+                value: 1.0,
+                std_error: 0.0,
+            }],
+            log_likelihood: 0.0,
+        }
+    }
+
+    // the unit exponential time at which its cdf reaches u
+    fn at(u: f64) -> f64 {
+        -(1.0 - u).ln()
+    }
+
+    // counts[j] points in the middle of the j-th equiprobable bin
+    fn binned(counts: &[usize]) -> Vec<f64> {
+        let bins = counts.len() as f64;
+        counts
+            .iter()
+            .enumerate()
+            .flat_map(|(j, count)| vec![at((j as f64 + 0.5) / bins); *count])
+            .collect()
+    }
+
+    #[test]
+    fn chi_square_counts_each_bin() {
+        // 5 bins less 1 less the rate leaves 3 degrees of freedom,
+        // whose 5% critical value in the tables is 7.815
+        let data = binned(&[17, 5, 8, 10, 10]);
+        let result = chi_square(&data, &unit_exponential(), 5);
+        assert!((result.statistic - 7.8).abs() < 1e-12);
+        assert!(
+            result.p_value > 0.05 && result.p_value < 0.051,
+            "{result:?}"
+        );
+
+        // with 2 degrees of freedom the survival is exp(-x / 2)
+        let data = binned(&[18, 6, 8, 8]);
+        let result = chi_square(&data, &unit_exponential(), 4);
+        assert!((result.statistic - 8.8).abs() < 1e-12);
+        assert!(
+            (result.p_value - (-4.4f64).exp()).abs() < 1e-9,
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn kolmogorov_smirnov_matches_the_tables() {
+        // n points at cdf a * i / n are furthest from the
+        // empirical cdf just below the last, by 1 - a. Stephens'
+        // modified statistic has critical values 1.224, 1.358
+        // and 1.628 at 10%, 5% and 1%
+        let n = 20;
+        let root = (n as f64).sqrt();
+        for (critical, alpha) in [(1.224, 0.10), (1.358, 0.05), (1.628, 0.01)] {
+            let distance = critical / (root + 0.12 + 0.11 / root);
+            let data = (1..=n)
+                .map(|i| at((1.0 - distance) * i as f64 / n as f64))
+                .collect::<Vec<f64>>();
+            let result = kolmogorov_smirnov(&data, &unit_exponential());
+            assert!((result.statistic - distance).abs() < 1e-12);
+            assert!((result.p_value - alpha).abs() < 5e-4, "{result:?}");
+        }
+    }
+}
@@ -1,6 +1,5 @@
 use std::cell::RefCell;
 use std::collections::VecDeque;
-use std::fmt::{Display, Formatter, Result};
 use std::rc::Rc;
 
 #[macro_use]
@@ -19,196 +18,182 @@ mod logging {
     }
 }
 
+#[macro_use]
+mod choices;
+
+mod allocation;
+mod batch;
+mod checkpoint;
+mod cli;
 mod component;
+mod control;
+mod distribution;
 mod event;
+mod fitting;
+mod generators;
+mod gof;
 mod inspector;
+mod plots;
 mod product;
 mod random;
+mod report;
+mod results;
+mod rngtest;
+mod scenario;
 mod simulation;
+mod special;
+mod statistics;
+mod topology;
+mod trace;
+mod warmup;
 mod workstation;
 
-use component::Component;
-use event::FacilityEvent;
-use event::FacilityEvent::*;
+use checkpoint::{Checkpoint, CheckpointError, Reader, Writer};
+use distribution::Distribution;
 use inspector::*;
 use product::Product;
-use random::Random;
+use random::{Generator, Random};
+use results::{Measure, ReplicationResult};
+use scenario::Scenario;
 use simulation::Duration;
+use simulation::FacilitySimulation;
 use simulation::SimulationActor;
 use simulation::TimeStamp;
+use topology::{Kind, Topology};
+use trace::Traces;
+use workstation::Buffer;
 use workstation::Type as WSType;
 use workstation::Workstation;
 
-// this enum is basically just a spaghetti code handler
-// to work around the fact that I couldn't figure out how to coerce
-// the Workstation and Inspector structs into SimulationActors
-// and just have actors be a Rc<RefCell<dyn SimulationActor>>
-enum Actor<'a> {
-    Ptr(&'a mut dyn Inspector),
-    Val(Rc<RefCell<Workstation>>),
+fn get_durations(
+    streams: &mut [Random],
+    distributions: &[Box<dyn Distribution>],
+    count: usize,
+) -> Vec<VecDeque<Duration>> {
+    // draws count service times from each distribution,
+    // each with its own random stream
+    distributions
+        .iter()
+        .zip(streams.iter_mut())
+        .map(|(distribution, rand)| {
+            (0..count)
+                .map(|_| distribution.sample(rand))
+                .collect::<VecDeque<Duration>>()
+        })
+        .collect()
 }
 
-impl<'a> Actor<'a> {
-    fn ptr(p: &'a mut dyn Inspector) -> Self {
-        Actor::Ptr(p)
-    }
-
-    fn val(v: Rc<RefCell<Workstation>>) -> Self {
-        Actor::Val(v)
-    }
-
-    fn respond_to(&mut self, event: FacilityEvent) -> Option<FacilityEvent> {
-        match self {
-            Actor::Ptr(p) => (p as &mut dyn SimulationActor).respond_to(event),
-            Actor::Val(v) => v.borrow_mut().respond_to(event),
-        }
-    }
-
-    fn respond(&mut self, now: TimeStamp) -> Option<FacilityEvent> {
-        match self {
-            Actor::Ptr(p) => (p as &mut dyn SimulationActor).respond(now),
-            Actor::Val(v) => v.borrow_mut().respond(now),
-        }
-    }
-
-    fn duration_until_next_event(&self, now: TimeStamp) -> Option<Duration> {
-        match self {
-            Actor::Ptr(p) => (p as &dyn SimulationActor).duration_until_next_event(now),
-            Actor::Val(v) => v.borrow_mut().duration_until_next_event(now),
-        }
-    }
+// every source of randomness in the model draws from its own
+// stream, positioned at the substream of the replication. the
+// same seed and replication always give the same numbers to the
+// same element, whatever the other elements do with theirs
+struct Streams {
+    assembly: Vec<Random>,
+    inspection: Vec<Random>,
+    // the choice between kinds of every inspector of several
+    policy: Vec<Random>,
 }
 
-impl<'a> Display for Actor<'a> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        match self {
-            Self::Ptr(ins) => write!(f, "{}", ins),
-            Self::Val(ws) => write!(f, "{}", ws.borrow()),
+impl Streams {
+    fn new(
+        topology: &Topology,
+        generator: Generator,
+        seed: u32,
+        replication: u64,
+        antithetic: bool,
+    ) -> Self {
+        let policies = topology
+            .inspectors
+            .iter()
+            .filter(|inspector| inspector.components.len() > 1)
+            .count();
+        let count = topology.workstations.len() + topology.components.len() + policies;
+        let mut streams = Random::streams(generator, seed, count)
+            .into_iter()
+            .map(|mut stream| {
+                // the replications of a run are checked against the
+                // substreams when it is set up
+                stream
+                    .advance_substreams(replication)
+                    .expect("a replication beyond the substreams of its streams");
+                stream.set_antithetic(antithetic);
+                stream
+            });
+        Streams {
+            assembly: streams.by_ref().take(topology.workstations.len()).collect(),
+            inspection: streams.by_ref().take(topology.components.len()).collect(),
+            policy: streams.collect(),
         }
     }
 }
 
-struct FacilitySimulation<'a> {
-    actors: Vec<Actor<'a>>,
-    clock: TimeStamp,
+// the distributions service times are sampled from for the
+// assembly of each workstation and the inspection of each component
+struct InputModel {
+    topology: Rc<Topology>,
+    assembly: Vec<Box<dyn Distribution>>,
+    inspection: Vec<Box<dyn Distribution>>,
 }
 
-impl FacilitySimulation<'_> {
-    fn time_until_next_actor_event(&self, now: TimeStamp) -> Option<(usize, Duration)> {
-        // returns the index of the actor
-        // who will produce the next event
-        // along with the duration until that event
-        // should be dispatched
-
-        let response_times: Vec<Duration> = self
-            .actors
-            .iter()
-            .map(|a| {
-                a.duration_until_next_event(now)
-                    .unwrap_or(Duration::never())
+impl InputModel {
+    fn new(
+        topology: Rc<Topology>,
+        traces: Option<&Traces>,
+        specs: &[Option<String>],
+    ) -> std::result::Result<Self, String> {
+        // streams without a spec get the topology's, which for the built-in
+        // facility is the exponential distribution fitted to their data,
+        // so the model follows the .dat files
+        let services = topology.services();
+        let observed = match traces {
+            Some(traces) => traces
+                .named()
+                .into_iter()
+                .map(|(_, durations)| trace::as_minutes(durations))
+                .collect(),
+            None => vec![vec![]; services.len()],
+        };
+        let mut assembly = services
+            .into_iter()
+            .zip(specs.iter().zip(observed))
+            .map(|(service, (spec, data))| {
+                let spec = spec.as_deref().unwrap_or(&service.spec);
+                distribution::parse(spec, &data)
+                    .map_err(|e| format!("{} service times: {e}", service.stream))
             })
-            .collect();
-        let mut min = (0, Duration::never(), false);
-        for (idx, val) in response_times.iter().enumerate() {
-            log!("actor: {:?}, {}\t{}", idx, val, self.actors[idx]);
-            if !val.as_minutes().is_infinite() && val < &min.1 {
-                min.0 = idx;
-                min.1 = *val;
-                min.2 = true;
-            }
-        }
-        log!("Next event in: {}, actor: {}\n\n", min.1, min.0);
-        match min.2 {
-            false => None,
-            true => Some((min.0, min.1)),
-        }
-    }
-
-    pub fn run(mut self) -> Duration {
-        // runs a simulation to completion, consuming this
-        // simulation structure and returns a vector of
-        // the events which are tracked as output events
-        self.dispatch_to_simulation_actors(SimulationStarted);
-        while let Some((next_actor_index, duration)) = self.time_until_next_actor_event(self.clock)
-        {
-            self.clock += duration;
-            log!("Time: {}", self.clock);
-            let responses = self.actors[next_actor_index].respond(self.clock);
-            for response in responses.into_iter() {
-                self.dispatch_to_simulation_actors(response);
-            }
-        }
-        self.clock - TimeStamp::start()
-    }
-
-    fn dispatch_to_simulation_actors(&mut self, event: FacilityEvent) {
-        for actor in self.actors.iter_mut() {
-            actor.respond_to(event);
-        }
+            .collect::<std::result::Result<Vec<Box<dyn Distribution>>, String>>()?;
+        let inspection = assembly.split_off(topology.workstations.len());
+        Ok(InputModel {
+            topology,
+            assembly,
+            inspection,
+        })
     }
 }
 
-fn get_assembly_durations(rand: &mut Random) -> [VecDeque<Duration>; 3] {
-    const WS1_LAMBDA: f64 = 0.217;
-    const WS2_LAMBDA: f64 = 0.090;
-    const WS3_LAMBDA: f64 = 0.114;
-
-    let inverse_cdf = |x: f64, lambda: f64| Duration::of_minutes(-(1.0 - x).ln() / lambda);
-
-    let ws1_dur: Vec<Duration> = (0..COMPONENT_COUNT)
-        .map(|_| inverse_cdf(rand.float(), WS1_LAMBDA))
-        .collect();
-    let ws2_dur: Vec<Duration> = (0..COMPONENT_COUNT)
-        .map(|_| inverse_cdf(rand.float(), WS2_LAMBDA))
-        .collect();
-    let ws3_dur: Vec<Duration> = (0..COMPONENT_COUNT)
-        .map(|_| inverse_cdf(rand.float(), WS3_LAMBDA))
-        .collect();
-    [ws1_dur.into(), ws2_dur.into(), ws3_dur.into()]
-}
-
-fn get_inspection_durations(mut rand: Random) -> [VecDeque<Duration>; 3] {
-    const I1_1_LAMBDA: f64 = 0.097;
-    const I2_2_LAMBDA: f64 = 0.064;
-    const I2_3_LAMBDA: f64 = 0.048;
-
-    let inverse_cdf = |x: f64, lambda: f64| Duration::of_minutes(-(1.0 - x).ln() / lambda);
-
-    let ins1_1: Vec<Duration> = (0..COMPONENT_COUNT)
-        .map(|_| inverse_cdf(rand.float(), I1_1_LAMBDA))
-        .collect();
-    let ins2_2: Vec<Duration> = (0..COMPONENT_COUNT)
-        .map(|_| inverse_cdf(rand.float(), I2_2_LAMBDA))
-        .collect();
-    let ins2_3: Vec<Duration> = (0..COMPONENT_COUNT)
-        .map(|_| inverse_cdf(rand.float(), I2_3_LAMBDA))
-        .collect();
-    [ins1_1.into(), ins2_2.into(), ins2_3.into()]
-}
-
 fn buffer_stats(
     ws: Rc<RefCell<Workstation>>,
-    component: Component,
+    component: Kind,
     start_time: f64,
     end_time: f64,
 ) -> f64 {
     // prints various buffer stas and returns the average occupancy
     let ws = ws.borrow();
 
-    let count_in_ws = |component: Component, w: WSType| match w
+    let count_in_ws = |component: &Kind, w: &WSType| match w
         .first_enqueue_time()
         .unwrap_or(TimeStamp::start())
         .get()
         > start_time
     {
-        true => w.matching_count(component) as f64,
+        true => w.waiting(component) as f64,
         false => 0.0,
     };
 
-    let count_arrival = |component: Component, w: &[(TimeStamp, WSType)]| {
+    let count_arrival = |component: &Kind, w: &[(TimeStamp, WSType)]| {
         // match if the number of components between state 2 and 1
         // increased (i.e, if there was an arrival)
-        match count_in_ws(component, w[0].1) < count_in_ws(component, w[1].1) {
+        match count_in_ws(component, &w[0].1) < count_in_ws(component, &w[1].1) {
             true => 1.0,
             false => 0.0,
         }
@@ -222,7 +207,7 @@ fn buffer_stats(
 
     let occupancy = (1.0 / (end_time - start_time))
         * ws.buffer_states.as_slice().windows(2).fold(0.0, |acc, w| {
-            acc + (w[1].0 - w[0].0).as_minutes() * count_in_ws(component, w[0].1)
+            acc + (w[1].0 - w[0].0).as_minutes() * count_in_ws(&component, &w[0].1)
         });
 
     log!(
@@ -234,7 +219,7 @@ fn buffer_stats(
         * ws.buffer_states
             .as_slice()
             .windows(2)
-            .fold(0.0, |acc, w| acc + count_arrival(component, w));
+            .fold(0.0, |acc, w| acc + count_arrival(&component, w));
     log!(
         "λ = {component} buffer entry throughput  of {} {:.5}",
         ws.name(),
@@ -242,7 +227,7 @@ fn buffer_stats(
     );
     let wait_time = (1.0 / products.len() as f64)
         * products.iter().fold(Duration::none(), |acc, product| {
-            acc + product.wait_time(component)
+            acc + product.wait_time(&component)
         });
     log!(
         "W = average {component} wait time of {} {:.2}",
@@ -256,31 +241,38 @@ fn buffer_stats(
     occupancy
 }
 
-fn ws_stats(ws: Rc<RefCell<Workstation>>, total_time: f64) -> f64 {
+fn ws_stats(ws: Rc<RefCell<Workstation>>, start_time: f64, end_time: f64) -> f64 {
+    // the proportion of the time after the warm-up the workstation
+    // could work, counting only the part of each state in the period
     let ws = ws.borrow();
 
     let add_working_time = |bs: &[(TimeStamp, WSType)]| {
-        // println!("{} {} {}", bs[1].0, bs[0].0, bs[0].1 );
-        match bs[0].1.can_work() {
-            true => bs[1].0 - bs[0].0,
-            false => Duration::none(),
+        let from = bs[0].0.get().max(start_time);
+        let to = bs[1].0.get().min(end_time);
+        match bs[0].1.can_work() && to > from {
+            true => to - from,
+            false => 0.0,
         }
     };
 
     ws.buffer_states
         .windows(2)
-        .fold(Duration::none(), |acc, bs| acc + add_working_time(bs))
-        .as_minutes()
-        / total_time
+        .fold(0.0, |acc, bs| acc + add_working_time(bs))
+        / (end_time - start_time)
 }
 
 fn product_stats(p: Vec<Product>, start_time: f64, end_time: f64) -> f64 {
-    // calculates product throughput
-    log!("Total {}: {}", p[0].name(), p.len());
-    p.len() as f64 / (end_time - start_time)
+    // calculates product throughput, counting the
+    // products finished after the warm-up
+    log!("Total {}: {}", p.first().map_or("", |p| p.name()), p.len());
+    let finished = p
+        .iter()
+        .filter(|p| p.timestamp().get() > start_time && p.timestamp().get() <= end_time)
+        .count();
+    finished as f64 / (end_time - start_time)
 }
 
-fn inspector_stats(ins: &dyn Inspector, start_time: f64, end_time: f64) -> f64 {
+fn inspector_stats(ins: &Inspector, start_time: f64, end_time: f64) -> f64 {
     // gets the proportion of time for which an inspector was blocked.
     let mut i = 0;
     let mut add_next_slice = |w: &[&TimeStamp]| {
@@ -303,15 +295,28 @@ fn inspector_stats(ins: &dyn Inspector, start_time: f64, end_time: f64) -> f64 {
 }
 
 fn littles_law_whole_system(
-    inspection_times: [&mut VecDeque<TimeStamp>; 2],
-    exit_times: [&mut VecDeque<TimeStamp>; 2],
-    products: [&Vec<Product>; 3],
+    inspection_times: Vec<&[TimeStamp]>,
+    products: Vec<&Vec<Product>>,
     start_time: f64,
     end_time: f64,
 ) -> f64 {
     // checks that little law holds for the whole system and
     // returns the total average buffer occupancy
 
+    // components enter one at a time when their inspection starts, in
+    // the lists of each inspector, and leave in the products of each
+    // workstation, every list being in time order
+    let mut v = inspection_times
+        .iter()
+        .map(|times| times.iter().map(|ts| (*ts, 1)).collect())
+        .chain(products.iter().map(|products| {
+            products
+                .iter()
+                .map(|p| (p.timestamp(), -(p.component_count() as i64)))
+                .collect()
+        }))
+        .collect::<Vec<VecDeque<(TimeStamp, i64)>>>();
+
     // combine all the products into one list
     // and filter those occuring before start_time
     let products = products
@@ -336,25 +341,15 @@ fn littles_law_whole_system(
             })
             .as_minutes();
 
-    let min_idx = |v: &[&mut VecDeque<TimeStamp>; 4]| -> Option<usize> {
-        let idx = v
-            .iter()
+    // the list with the earliest next time, skipping any which ran out
+    let min_idx = |v: &[VecDeque<(TimeStamp, i64)>]| -> Option<usize> {
+        v.iter()
             .enumerate()
-            .min_by(|(_, v0), (_, v1)| v0.partial_cmp(&v1).unwrap())
+            .filter_map(|(idx, times)| times.front().map(|(ts, _)| (idx, *ts)))
+            .min_by(|(_, t0), (_, t1)| t0.partial_cmp(t1).unwrap())
             .map(|(idx, _)| idx)
-            .unwrap();
-        match v[idx].front() {
-            Some(_) => Some(idx),
-            None => None,
-        }
     };
 
-    let v = [
-        &mut inspection_times[0].clone(),
-        &mut inspection_times[1].clone(),
-        &mut exit_times[0].clone(),
-        &mut exit_times[1].clone(),
-    ];
     let mut t = start_time;
     let mut current_occupants = 0;
     let mut occupancy = 0.0;
@@ -364,20 +359,13 @@ fn littles_law_whole_system(
     while t < end_time {
         match min_idx(&v) {
             Some(idx) => {
-                let t2 = v[idx].pop_front().unwrap().get();
-                if idx == 0 || idx == 1 {
-                    current_occupants += 1;
-                    if t2 > start_time {
-                        arrival_rate.0 += t2 - arrival_rate.1;
-                        arrival_rate.1 = t2;
-                        arrival_rate.2 += 1;
-                    }
-                } else if idx == 2 {
-                    // P1 uses 1 C1
-                    current_occupants -= 1;
-                } else if idx == 3 {
-                    // P2 and P3 always use 2 components
-                    current_occupants -= 2;
+                let (t2, change) = v[idx].pop_front().unwrap();
+                let t2 = t2.get();
+                current_occupants += change;
+                if change > 0 && t2 > start_time {
+                    arrival_rate.0 += t2 - arrival_rate.1;
+                    arrival_rate.1 = t2;
+                    arrival_rate.2 += 1;
                 }
                 if t > start_time {
                     occupancy += current_occupants as f64 * (t2 - t);
@@ -388,7 +376,7 @@ fn littles_law_whole_system(
         }
     }
 
-    occupancy = occupancy / (end_time - start_time);
+    occupancy /= end_time - start_time;
     // arrival rate = total arrivals / total time between arrivals
     let arrival_rate = arrival_rate.2 as f64 / arrival_rate.0;
 
@@ -405,260 +393,599 @@ fn littles_law_whole_system(
 
 const INIT_R: usize = 10;
 const MAX_R: usize = 200;
+// replications use successive substreams of the streams this seeds
+const SEED: u32 = 1;
+// the minutes deleted from the start of a replication unless a scenario
+// or --warmup gives another
+const WARMUP: f64 = 600.0;
+// the intervals and Welch window warm-up detection uses by default
+const WARMUP_INTERVAL: f64 = 100.0;
+const WARMUP_WINDOW: usize = 5;
 pub const COMPONENT_COUNT: usize = 3000;
+const CHECKPOINT_VERSION: u32 = 5;
+// the places in each buffer unless a scenario or --capacities gives them,
+// or as many as one product takes from it if that is more
+const CAPACITY: usize = 2;
+
+// where the service times of a replication come from
+#[derive(Clone, Copy)]
+enum ServiceTimes<'a> {
+    // sampled from the input model
+    Generated(&'a InputModel),
+    // replayed exactly as observed in the .dat files
+    Trace(&'a Traces),
+}
 
-fn run_iteration(seed: u32, start_time: f64) -> [Vec<f64>; 5] {
-    let mut rand = Random::with_seed(seed);
-    let assembly_durations: [VecDeque<Duration>; 3] = get_assembly_durations(&mut rand);
-    let ws1 = Rc::new(RefCell::new(Workstation::new(
-        WSType::W1([None, None]),
-        assembly_durations[0].clone(),
-    )));
-    let ws2 = Rc::new(RefCell::new(Workstation::new(
-        WSType::W2([None, None], [None, None]),
-        assembly_durations[1].clone(),
-    )));
-    let ws3 = Rc::new(RefCell::new(Workstation::new(
-        WSType::W3([None, None], [None, None]),
-        assembly_durations[2].clone(),
-    )));
-
-    let inspect_durations: [VecDeque<Duration>; 3] = get_inspection_durations(rand);
-    let mut inspector1 = Inspector1::new(
-        [ws1.clone(), ws2.clone(), ws3.clone()],
-        inspect_durations[0].clone(),
-    );
-    let mut inspector2 = Inspector2::new(
-        [ws2.clone(), ws3.clone()],
-        inspect_durations[1].clone(),
-        inspect_durations[2].clone(),
-    );
+// what stays the same across the replications of a run
+#[derive(Clone)]
+struct RunConfig<'a> {
+    topology: Rc<Topology>,
+    generator: Generator,
+    seed: u32,
+    routing: Routing,
+    // mirrors every uniform, so replication r of this run is
+    // the antithetic partner of replication r of a plain one
+    antithetic: bool,
+    // the minutes deleted from the start of every replication
+    warmup: f64,
+    // the service times drawn for each stream, which sets the run length
+    components: usize,
+    service_times: ServiceTimes<'a>,
+    // the places in each of Topology::buffers
+    capacities: &'a [usize],
+}
 
-    let actors: Vec<Actor> = vec![
-        Actor::val(ws1.clone()),
-        Actor::val(ws2.clone()),
-        Actor::val(ws3.clone()),
-        Actor::ptr(&mut inspector1 as &mut dyn Inspector),
-        Actor::ptr(&mut inspector2 as &mut dyn Inspector),
-    ];
-    let simulation = FacilitySimulation {
-        actors,
-        clock: TimeStamp::start(),
-    };
+impl<'a> RunConfig<'a> {
+    fn new(scenario: &'a Scenario, service_times: ServiceTimes<'a>) -> Self {
+        // the scenario's warm-up is resolved separately, as detecting
+        // it needs a config to run
+        RunConfig {
+            topology: scenario.topology.clone(),
+            generator: scenario.generator,
+            seed: scenario.seed,
+            routing: scenario.routing,
+            antithetic: false,
+            warmup: WARMUP,
+            components: scenario.components,
+            service_times,
+            capacities: &scenario.capacities,
+        }
+    }
+}
 
-    // run the simulation and get the first a workstation
-    // finished its last product as the end time
-    simulation.run();
-    let last_ws_log =
-        |ws: &Rc<RefCell<Workstation>>| ws.borrow().products.last().unwrap().timestamp().get();
-    let end_time = *[last_ws_log(&ws3), last_ws_log(&ws1), last_ws_log(&ws2)]
-        .iter()
-        .min_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap();
-    log!(
-        "Finished simulation start: {:.2} end: {:.2}", start_time, end_time
-    );
+// the actors of one replication, kept by type so that
+// they can be measured or checkpointed while it runs
+struct Facility {
+    topology: Rc<Topology>,
+    // in the order of the topology's lists
+    ws: Vec<Rc<RefCell<Workstation>>>,
+    inspectors: Vec<Rc<RefCell<Inspector>>>,
+    simulation: FacilitySimulation,
+    // the mean of the service times each stream was given, in
+    // the order of Topology::services. these are not checkpointed
+    service_means: Vec<f64>,
+}
 
-    // calculate stats for all 5 buffers
-    let buffer_stats = [
-        buffer_stats(
-            ws1.clone(),
-            Component::new(Duration::never(), 1),
-            start_time,
-            end_time,
-        ),
-        buffer_stats(
-            ws2.clone(),
-            Component::new(Duration::never(), 1),
-            start_time,
-            end_time,
-        ),
-        buffer_stats(
-            ws2.clone(),
-            Component::new(Duration::never(), 2),
-            start_time,
-            end_time,
-        ),
-        buffer_stats(
-            ws3.clone(),
-            Component::new(Duration::never(), 1),
-            start_time,
-            end_time,
-        ),
-        buffer_stats(
-            ws3.clone(),
-            Component::new(Duration::never(), 3),
-            start_time,
-            end_time,
-        ),
-    ];
-    log!("Average buffer occupancies: {:.2?}", buffer_stats);
+impl Facility {
+    fn new(
+        topology: Rc<Topology>,
+        assembly_durations: Vec<VecDeque<Duration>>,
+        inspect_durations: Vec<VecDeque<Duration>>,
+        policies: Vec<Random>,
+        routing: Routing,
+        capacities: &[usize],
+    ) -> Self {
+        let buffers = topology.buffers();
+        let ws = (0..topology.workstations.len())
+            .map(|w| {
+                let buffers = buffers
+                    .iter()
+                    .zip(capacities)
+                    .filter(|(buffer, _)| buffer.workstation == w)
+                    .map(|(buffer, capacity)| {
+                        Buffer::new(
+                            topology.component(buffer.component),
+                            buffer.needed,
+                            *capacity,
+                        )
+                    })
+                    .collect();
+                Rc::new(RefCell::new(Workstation::new(
+                    WSType::new(topology.workstation(w), buffers),
+                    topology.product(topology.workstations[w].product),
+                    assembly_durations[w].clone(),
+                )))
+            })
+            .collect::<Vec<Rc<RefCell<Workstation>>>>();
 
-    // calculate stats for each WS
-    let ws_stats = [
-        ws_stats(ws1.clone(), end_time),
-        ws_stats(ws2.clone(), end_time),
-        ws_stats(ws3.clone(), end_time),
-    ];
-    log!("WS working rate [WS1, WS2, WS3] {:.2?}", ws_stats);
-
-    // calculate stats for each product
-    let product_stats = [
-        product_stats(ws1.borrow().products.clone(), start_time, end_time),
-        product_stats(ws2.borrow().products.clone(), start_time, end_time),
-        product_stats(ws3.borrow().products.clone(), start_time, end_time),
-    ];
-    log!("Product throughput [P1, P2, P3] {:.2?}", product_stats);
+        // only an inspector of several kinds has a choice to draw
+        let mut policies = policies.into_iter();
+        let inspectors = topology
+            .inspectors
+            .iter()
+            .map(|inspector| {
+                let components = &inspector.components;
+                // the workstations using each component, in order
+                let destinations = components
+                    .iter()
+                    .map(|c| {
+                        (0..ws.len())
+                            .filter(|w| topology.uses(*w, *c))
+                            .map(|w| ws[w].clone())
+                            .collect()
+                    })
+                    .collect();
+                Rc::new(RefCell::new(Inspector::new(
+                    inspector.name.clone(),
+                    components.iter().map(|c| topology.component(*c)).collect(),
+                    components
+                        .iter()
+                        .map(|c| inspect_durations[*c].clone())
+                        .collect(),
+                    destinations,
+                    routing,
+                    match components.len() > 1 {
+                        true => policies.next(),
+                        false => None,
+                    },
+                )))
+            })
+            .collect::<Vec<Rc<RefCell<Inspector>>>>();
 
-    // calculate stats for each inspector
-    let inspector_stats = [
-        inspector_stats(&inspector1, start_time, end_time),
-        inspector_stats(&inspector2, start_time, end_time),
-    ];
-    log!(
-        "Inspector blocked rate [Ins1, Ins2] {:.2?}",
-        inspector_stats
-    );
+        let mean = |durations: &VecDeque<Duration>| {
+            durations.iter().map(|d| d.as_minutes()).sum::<f64>() / durations.len() as f64
+        };
+        let service_means = assembly_durations
+            .iter()
+            .chain(inspect_durations.iter())
+            .map(mean)
+            .collect();
 
-    let ins1 = inspector1.inspection_times();
-    let ins2 = inspector2.inspection_times();
-    let total_average_occupancy = littles_law_whole_system(
-        [ins1.0, ins2.0],
-        [ins1.1, ins2.1],
-        [
-            &ws1.borrow().products,
-            &ws2.borrow().products,
-            &ws3.borrow().products,
-        ],
-        start_time,
-        end_time,
-    );
+        let actors: Vec<Rc<RefCell<dyn SimulationActor>>> = ws
+            .iter()
+            .map(|ws| ws.clone() as Rc<RefCell<dyn SimulationActor>>)
+            .chain(
+                inspectors
+                    .iter()
+                    .map(|inspector| inspector.clone() as Rc<RefCell<dyn SimulationActor>>),
+            )
+            .collect();
+        Facility {
+            topology,
+            ws,
+            inspectors,
+            simulation: FacilitySimulation::new(actors),
+            service_means,
+        }
+    }
 
-    [
-        buffer_stats.to_vec(),
-        ws_stats.to_vec(),
-        product_stats.to_vec(),
-        inspector_stats.to_vec(),
-        vec!(total_average_occupancy)
-    ]
+    fn generate(config: &RunConfig, replication: u64) -> Self {
+        let mut streams = Streams::new(
+            &config.topology,
+            config.generator,
+            config.seed,
+            replication,
+            config.antithetic,
+        );
+        let assembly_durations = match config.service_times {
+            ServiceTimes::Generated(model) => {
+                get_durations(&mut streams.assembly, &model.assembly, config.components)
+            }
+            ServiceTimes::Trace(traces) => traces.assembly.to_vec(),
+        };
+        let inspect_durations = match config.service_times {
+            ServiceTimes::Generated(model) => get_durations(
+                &mut streams.inspection,
+                &model.inspection,
+                config.components,
+            ),
+            ServiceTimes::Trace(traces) => traces.inspection.to_vec(),
+        };
+        Facility::new(
+            config.topology.clone(),
+            assembly_durations,
+            inspect_durations,
+            streams.policy,
+            config.routing,
+            config.capacities,
+        )
+    }
+
+    fn empty(topology: Rc<Topology>) -> Self {
+        // a facility with nothing to simulate, for a checkpoint
+        // to be restored into
+        Facility::new(
+            topology.clone(),
+            vec![VecDeque::new(); topology.workstations.len()],
+            vec![VecDeque::new(); topology.components.len()],
+            Streams::new(&topology, Generator::Lcg, SEED, 0, false).policy,
+            Routing::New,
+            &vec![CAPACITY; topology.buffers().len()],
+        )
+    }
 }
 
-fn std_dev(v: &Vec<f64>) -> f64 {
-    match v.len() < INIT_R {
-        true => f64::INFINITY,
-        false => {
-            let avg: f64 = v.iter().sum::<f64>() / v.len() as f64;
-            v.iter().fold(0.0, |acc, vi| acc + (vi - avg).abs()) / (v.len() as f64 - 1.0)
+impl Checkpoint for Facility {
+    fn save(&self, out: &mut Writer) {
+        out.line("checkpoint").value(CHECKPOINT_VERSION);
+        self.simulation.save(out);
+        for ws in self.ws.iter() {
+            ws.borrow().save(out);
+        }
+        for inspector in self.inspectors.iter() {
+            inspector.borrow().save(out);
+        }
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> std::result::Result<(), CheckpointError> {
+        input.expect("checkpoint")?;
+        let version = input.value::<u32>()?;
+        if version != CHECKPOINT_VERSION {
+            return Err(input.error(format!("version {version} checkpoints are not supported")));
         }
+        self.simulation.restore(input)?;
+        for ws in self.ws.iter() {
+            ws.borrow_mut().restore(input)?;
+        }
+        for inspector in self.inspectors.iter() {
+            inspector.borrow_mut().restore(input)?;
+        }
+        input.finish()
     }
 }
 
-fn main() {
+fn run_iteration(
+    config: &RunConfig,
+    replication: u64,
+) -> std::result::Result<ReplicationResult, String> {
+    let mut facility = Facility::generate(config, replication);
+    facility.simulation.run();
+    facility_stats(&facility, config.warmup)
+}
 
-    let mut i = 0;
-    let start_time = 600.0;
-    let mut cumulative_stats = [vec![0.0; 5], vec![0.0; 3], 
-        vec![0.0; 3], vec![0.0; 2], vec![0.0]].to_vec();
-
-    let e = 0.02; // 𝜀
-    let z_025 = 1.960; // 95% confidence
-    let mut y: Vec<Vec<f64>> = vec![vec!(); 14];
-    let mut cumulative_std_dev: Vec<Vec<f64>> = vec![vec!(); 14];
-    let mut n = 0;
-
-    let calculate_total_throughput = |throughput: Vec<f64>| {
-        (throughput[0] + throughput[1] * 2.0 + throughput[2] * 2.0) / 3.0
-    };
+fn end_time(facility: &Facility) -> f64 {
+    // the time the first workstation finished its last product,
+    // leaving out any which never made one, i.e with no buffer space.
+    // when none did, the time the simulation ran out of events
+    facility
+        .ws
+        .iter()
+        .filter_map(|ws| ws.borrow().products.last().map(|p| p.timestamp().get()))
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or(facility.simulation.clock().get())
+}
 
-    for r in 0..MAX_R {
-        n += 1;
-        let stats = run_iteration(n as u32, start_time);
-        let total_throughput = 
-            calculate_total_throughput(stats[2].clone());
-        println!("{n} \t {total_throughput}");
-        for (i, v) in stats.iter().enumerate() {
-            for i2 in 0..v.len() {
-                cumulative_stats[i][i2] += v[i2];
-            }
-        }
+fn output_series(facility: &Facility, interval: f64) -> [Vec<f64>; 2] {
+    // the product completion rate and the total buffer
+    // occupancy of a finished replication in each interval
+    let bins = (end_time(facility) / interval).floor() as usize;
+    let completions = facility
+        .ws
+        .iter()
+        .flat_map(|ws| {
+            ws.borrow()
+                .products
+                .iter()
+                .map(|p| p.timestamp().get())
+                .collect::<Vec<f64>>()
+        })
+        .collect::<Vec<f64>>();
+
+    // merge the buffer states of the workstations into one step function
+    let mut changes = facility
+        .ws
+        .iter()
+        .enumerate()
+        .flat_map(|(i, ws)| {
+            ws.borrow()
+                .buffer_states
+                .iter()
+                .map(|(ts, state)| (ts.get(), i, state.occupancy()))
+                .collect::<Vec<(f64, usize, usize)>>()
+        })
+        .collect::<Vec<(f64, usize, usize)>>();
+    changes.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let mut occupancy = vec![0; facility.ws.len()];
+    let steps = changes
+        .into_iter()
+        .map(|(t, i, count)| {
+            occupancy[i] = count;
+            (t, occupancy.iter().sum::<usize>() as f64)
+        })
+        .collect::<Vec<(f64, f64)>>();
 
-        // number to compare against n to determine
-        // the number of replications
-        let mut calculated_r: Vec<f64> = vec![];
+    [
+        warmup::completion_rate(&completions, interval, bins),
+        warmup::time_average(&steps, interval, bins),
+    ]
+}
 
-        for (i, stat) in stats
-            .into_iter()
-            .flatten()
-            .collect::<Vec<f64>>()
-            .into_iter()
-            .enumerate()
-        {
-            y[i].push(stat);
-            calculated_r.push((std_dev(&y[i]) * z_025 / e).powf(2.0));
-            cumulative_std_dev[i].push(std_dev(&y[i]));
+fn batch_series(facility: &Facility, bounds: &[f64]) -> Vec<ReplicationResult> {
+    // every measure of run_iteration between
+    // each pair of bounds of a finished run
+    let topology = &facility.topology;
+    let ws = facility.ws.iter().map(|ws| ws.borrow()).collect::<Vec<_>>();
+    let states = |i: usize, value: &dyn Fn(&WSType) -> f64| {
+        ws[i]
+            .buffer_states
+            .iter()
+            .map(|(ts, state)| (ts.get(), value(state)))
+            .collect::<Vec<(f64, f64)>>()
+    };
+    let mut results = vec![ReplicationResult::new(topology); bounds.len() - 1];
+    let mut fill = |measure: Measure, series: Vec<f64>| {
+        for (result, value) in results.iter_mut().zip(series) {
+            result.set(measure, value);
         }
+    };
 
-        if r > INIT_R && !(calculated_r.iter().any(|cr| *cr as f64 > r as f64)) {
-            println!("\nConverged on replication count (R) of {r}");
-            break;
+    for (b, buffer) in topology.buffers().iter().enumerate() {
+        let component = topology.component(buffer.component);
+        let steps = states(buffer.workstation, &|state| {
+            state.waiting(&component) as f64
+        });
+        fill(Measure::Occupancy(b), batch::step_averages(&steps, bounds));
+    }
+    for i in 0..ws.len() {
+        let steps = states(i, &|state| match state.can_work() {
+            true => 1.0,
+            false => 0.0,
+        });
+        fill(
+            Measure::Utilization(i),
+            batch::step_averages(&steps, bounds),
+        );
+    }
+    for p in 0..topology.products.len() {
+        // the completions of every workstation assembling the product
+        let mut completions = ws
+            .iter()
+            .zip(topology.workstations.iter())
+            .filter(|(_, def)| def.product == p)
+            .flat_map(|(ws, _)| ws.products.iter().map(|p| p.timestamp().get()))
+            .collect::<Vec<f64>>();
+        completions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        fill(Measure::Throughput(p), batch::rates(&completions, bounds));
+    }
+    for (j, inspector) in facility.inspectors.iter().enumerate() {
+        // an inspector starts blocked and every time toggles it
+        let mut steps = vec![(0.0, 1.0)];
+        for (i, ts) in inspector.borrow().blocked_times().iter().enumerate() {
+            steps.push((ts.get(), (i % 2) as f64));
         }
+        fill(Measure::Blocking(j), batch::step_averages(&steps, bounds));
     }
 
+    // components enter when inspected and leave in their product
+    let mut changes = vec![];
+    for inspector in facility.inspectors.iter() {
+        changes.extend(
+            inspector
+                .borrow()
+                .inspection_times()
+                .iter()
+                .map(|ts| (ts.get(), 1.0)),
+        );
+    }
+    for ws in ws.iter() {
+        changes.extend(
+            ws.products
+                .iter()
+                .map(|p| (p.timestamp().get(), -(p.component_count() as f64))),
+        );
+    }
+    changes.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let mut total = 0.0;
+    let steps = changes
+        .into_iter()
+        .map(|(t, change)| {
+            total += change;
+            (t, total)
+        })
+        .collect::<Vec<(f64, f64)>>();
+    fill(Measure::Wip, batch::step_averages(&steps, bounds));
+    results
+}
 
-    let mut cumulative_stats = cumulative_stats.into_iter().flatten().collect::<Vec<f64>>();
-    for i in 0..cumulative_stats.len() {
-        cumulative_stats[i] = cumulative_stats[i] / n as f64;
+// the series output_series collects
+const SERIES: [&str; 2] = ["completion rate", "buffer occupancy"];
+
+fn detect_warmup(
+    config: &RunConfig,
+    replications: usize,
+    interval: f64,
+    window: usize,
+) -> ([Vec<f64>; 2], [f64; 2]) {
+    // the Welch moving average of each series over the replications,
+    // and the warm-up in minutes MSER-5 finds in its unsmoothed average
+    let mut series = [vec![], vec![]];
+    for r in 0..replications {
+        let mut facility = Facility::generate(config, r as u64);
+        facility.simulation.run();
+        for (all, one) in series.iter_mut().zip(output_series(&facility, interval)) {
+            all.push(one);
+        }
     }
+    let welch = series.each_ref().map(|s| warmup::welch(s, window));
+    let truncation = series
+        .each_ref()
+        .map(|s| warmup::mser(&warmup::welch(s, 0), 5) as f64 * interval);
+    (welch, truncation)
+}
 
-    let t_025 = [
-        2.262, 2.228, 2.201, 2.179, 
-        2.160, 2.145, 2.131, 2.120, 2.110,
-        2.101, 2.093, 2.086, 2.080, 2.074, 2.069,
-        2.064, 2.060, 2.056, 2.052, 2.048, 2.045,
-        2.042];
+fn auto_warmup(config: &RunConfig) -> f64 {
+    // the warm-up of a pilot run of INIT_R replications,
+    // long enough for the transient of both series to pass. it is
+    // reported on stderr, which keeps it out of CSV output
+    let (_, truncation) = detect_warmup(config, INIT_R, WARMUP_INTERVAL, WARMUP_WINDOW);
+    let warmup = truncation.into_iter().fold(0.0, f64::max);
+    eprintln!("Detected a warm-up of {warmup} minutes");
+    warmup
+}
 
+fn resolve_warmup(config: &mut RunConfig, warmup: Option<f64>) {
+    // None asks for the warm-up to be detected
+    config.warmup = warmup.unwrap_or_else(|| auto_warmup(config));
+}
 
-    let bound = |i: usize| -> f64 {
-        match n < 31 {
-            true => t_025[n-10] * cumulative_std_dev[i][cumulative_std_dev[i].len() - 1] / (n as f64).sqrt(),
-            false => z_025 * cumulative_std_dev[i][cumulative_std_dev[i].len() - 1] / (n as f64).sqrt()
-        }
-    };
+// i.e [WS1, WS2, WS3]
+fn bracketed<'a>(names: impl Iterator<Item = &'a String>) -> String {
+    let names = names.map(|name| name.as_str()).collect::<Vec<&str>>();
+    format!("[{}]", names.join(", "))
+}
 
-    const BUFFER_HEADERS: [&str; 5] = [
-        "C1 of WS1",
-        "C1 of WS2",
-        "C2 of WS2",
-        "C1 of WS3",
-        "C3 of WS3",
-    ];
-    log!(
-        "\nAverages for {n} replications with a \
-        queue size of {COMPONENT_COUNT} each"
-    );
-    log!("\nAverage occupancy for each buffer:");
-    for (i, h) in BUFFER_HEADERS.iter().enumerate() {
-        println!("{} {:#.2?} +- {:.5}", h, cumulative_stats[i], bound(i));
+fn facility_stats(
+    facility: &Facility,
+    start_time: f64,
+) -> std::result::Result<ReplicationResult, String> {
+    let topology = &facility.topology;
+
+    // get the first a workstation finished
+    // its last product as the end time
+    let end_time = end_time(facility);
+    if end_time <= start_time {
+        return Err(format!(
+            "the warmup of {start_time} minutes does not end before the run \
+            does at {end_time:.0}, give a shorter warmup or more components"
+        ));
     }
     log!(
-        "\n[W1, W2, W3] busy ratio : {:.2?} | CI [{:.4}, {:.4}, {:.4}]",
-        &cumulative_stats[5..8],
-        bound(5), bound(6), bound(7)
+        "Finished simulation start: {:.2} end: {:.2}", start_time, end_time
     );
+
+    // calculate stats for every buffer
+    let buffer_stats = topology
+        .buffers()
+        .iter()
+        .map(|buffer| {
+            buffer_stats(
+                facility.ws[buffer.workstation].clone(),
+                topology.component(buffer.component),
+                start_time,
+                end_time,
+            )
+        })
+        .collect::<Vec<f64>>();
+    log!("Average buffer occupancies: {:.2?}", buffer_stats);
+
+    // calculate stats for each WS
+    let ws_stats = facility
+        .ws
+        .iter()
+        .map(|ws| ws_stats(ws.clone(), start_time, end_time))
+        .collect::<Vec<f64>>();
     log!(
-        "\n[P1, P2, P3] throughput : {:.2?} | CI [{:.4}, {:.4}, {:.4}] ",
-        &cumulative_stats[8..11],
-        bound(8), bound(9), bound(10)
+        "WS working rate {} {:.2?}",
+        bracketed(topology.workstations.iter().map(|ws| &ws.name)),
+        ws_stats
     );
+
+    // calculate stats for each product, over
+    // every workstation which assembles it
+    let product_stats = (0..topology.products.len())
+        .map(|p| {
+            let products = facility
+                .ws
+                .iter()
+                .zip(topology.workstations.iter())
+                .filter(|(_, def)| def.product == p)
+                .flat_map(|(ws, _)| ws.borrow().products.clone())
+                .collect();
+            product_stats(products, start_time, end_time)
+        })
+        .collect::<Vec<f64>>();
     log!(
-        "\n[Inspector1, Inspector2] blocked ratio: {:.4?} | CI [{:.4}, {:.4}]",
-        &cumulative_stats[11..13], bound(11), bound(12)
+        "Product throughput {} {:.2?}",
+        bracketed(topology.products.iter().map(|p| &p.name)),
+        product_stats
     );
 
+    // calculate stats for each inspector
+    let inspector_stats = facility
+        .inspectors
+        .iter()
+        .map(|inspector| inspector_stats(&inspector.borrow(), start_time, end_time))
+        .collect::<Vec<f64>>();
     log!(
-        "\n Total Average Occupancy: {:.4?}",
-        &cumulative_stats[13]
+        "Inspector blocked rate {} {:.2?}",
+        bracketed(topology.inspectors.iter().map(|i| &i.name)),
+        inspector_stats
     );
 
+    let inspectors = facility
+        .inspectors
+        .iter()
+        .map(|inspector| inspector.borrow())
+        .collect::<Vec<_>>();
+    let ws = facility.ws.iter().map(|ws| ws.borrow()).collect::<Vec<_>>();
+    let total_average_occupancy = littles_law_whole_system(
+        inspectors.iter().map(|i| i.inspection_times()).collect(),
+        ws.iter().map(|ws| &ws.products).collect(),
+        start_time,
+        end_time,
+    );
+
+    Ok(ReplicationResult {
+        occupancy: buffer_stats,
+        utilization: ws_stats,
+        throughput: product_stats,
+        blocking: inspector_stats,
+        wip: total_average_occupancy,
+    })
+}
 
+fn main() {
+    cli::run(std::env::args().collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn resumed_checkpoint_ends_as_an_unbroken_run() {
+        // a replication saved part way and restored into an empty facility
+        // finishes exactly as the same replication run straight through
+        let scenario = Scenario::new(Rc::default());
+        let traces = Traces::load(Path::new(trace::DATA_DIR)).unwrap();
+        let model =
+            InputModel::new(scenario.topology.clone(), Some(&traces), &scenario.services).unwrap();
+        let config = RunConfig::new(&scenario, ServiceTimes::Generated(&model));
+        let unbroken = run_iteration(&config, 3).unwrap();
+
+        let mut saved = Facility::generate(&config, 3);
+        saved.simulation.start();
+        saved
+            .simulation
+            .run_until(TimeStamp::start() + Duration::of_minutes(5000.0));
+        let mut out = Writer::default();
+        saved.save(&mut out);
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.txt", std::process::id()));
+        out.write(&path).unwrap();
+
+        let mut resumed = Facility::empty(scenario.topology.clone());
+        let restored = Reader::read(&path, scenario.topology.clone())
+            .and_then(|mut input| resumed.restore(&mut input));
+        std::fs::remove_file(&path).unwrap();
+        restored.unwrap();
+        assert_eq!(resumed.simulation.clock(), saved.simulation.clock());
+        resumed.simulation.resume();
+        assert_eq!(facility_stats(&resumed, config.warmup).unwrap(), unbroken);
+    }
+
+    #[test]
+    fn checkpoint_of_another_version_is_rejected() {
+        let scenario = Scenario::new(Rc::default());
+        let path = std::env::temp_dir().join(format!("version-{}.txt", std::process::id()));
+        std::fs::write(&path, "checkpoint 1\n").unwrap();
+        let mut facility = Facility::empty(scenario.topology.clone());
+        let restored = Reader::read(&path, scenario.topology.clone())
+            .and_then(|mut input| facility.restore(&mut input));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            restored.unwrap_err().to_string(),
+            "checkpoint line 1: version 1 checkpoints are not supported"
+        );
+    }
 }
@@ -0,0 +1,435 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::fitting::Fit;
+
+// writes the data behind histograms, Q-Q plots and P-P plots
+// of the observed service times against their fitted distributions,
+// and the Welch plots of the simulation output used to choose the
+// warm-up. every plot is a csv file which can optionally be rendered
+// to a standalone svg so it can be checked by eye.
+
+const COLOURS: [&str; 5] = ["#d62728", "#2ca02c", "#9467bd", "#ff7f0e", "#8c564b"];
+
+pub fn write_plots(
+    dir: &Path,
+    stem: &str,
+    data: &[f64],
+    fits: &[Fit],
+    svg: bool,
+) -> io::Result<Vec<PathBuf>> {
+    // writes every plot for one data file, returning the files written
+    fs::create_dir_all(dir)?;
+    let mut written = vec![];
+    let mut write = |name: String, content: String| -> io::Result<()> {
+        let path = dir.join(name);
+        fs::write(&path, content)?;
+        written.push(path);
+        Ok(())
+    };
+
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let histogram = Histogram::new(&sorted);
+    write(format!("{stem}_histogram.csv"), histogram.csv(fits))?;
+    if svg {
+        write(format!("{stem}_histogram.svg"), histogram.svg(stem, fits))?;
+    }
+
+    for fit in fits {
+        let family = fit.family.name().to_lowercase();
+        let qq = qq_points(&sorted, fit);
+        let pp = pp_points(&sorted, fit);
+        write(
+            format!("{stem}_{family}_qq.csv"),
+            points_csv("theoretical,observed", &qq),
+        )?;
+        write(
+            format!("{stem}_{family}_pp.csv"),
+            points_csv("theoretical,empirical", &pp),
+        )?;
+        if svg {
+            let title = |kind: &str| format!("{stem} {kind} plot, {fit}");
+            write(
+                format!("{stem}_{family}_qq.svg"),
+                scatter_svg(&title("Q-Q"), "fitted quantile", "observed quantile", &qq),
+            )?;
+            write(
+                format!("{stem}_{family}_pp.svg"),
+                scatter_svg(
+                    &title("P-P"),
+                    "fitted probability",
+                    "empirical probability",
+                    &pp,
+                ),
+            )?;
+        }
+    }
+    Ok(written)
+}
+
+fn plotting_position(i: usize, n: usize) -> f64 {
+    (i as f64 + 0.5) / n as f64
+}
+
+fn qq_points(sorted: &[f64], fit: &Fit) -> Vec<(f64, f64)> {
+    sorted
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (fit.quantile(plotting_position(i, sorted.len())), *x))
+        .collect()
+}
+
+fn pp_points(sorted: &[f64], fit: &Fit) -> Vec<(f64, f64)> {
+    sorted
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (fit.cdf(*x), plotting_position(i, sorted.len())))
+        .collect()
+}
+
+pub fn write_welch(
+    dir: &Path,
+    names: &[&str],
+    interval: f64,
+    welch: &[Vec<f64>],
+    warmup: f64,
+    svg: bool,
+) -> io::Result<Vec<PathBuf>> {
+    // the Welch moving averages of the output series against time,
+    // with the chosen warm-up marked, returning the files written
+    fs::create_dir_all(dir)?;
+    let mut written = vec![];
+    let column = |name: &str| name.replace(' ', "_");
+    // each average is plotted at the middle of its interval
+    let time = |i: usize| (i as f64 + 0.5) * interval;
+
+    let mut csv = format!(
+        "minutes,{}\n",
+        names
+            .iter()
+            .map(|n| column(n))
+            .collect::<Vec<String>>()
+            .join(",")
+    );
+    for i in 0..welch.iter().map(|w| w.len()).min().unwrap_or(0) {
+        write!(csv, "{:.6}", time(i)).unwrap();
+        for series in welch {
+            write!(csv, ",{:.6}", series[i]).unwrap();
+        }
+        csv.push('\n');
+    }
+    let path = dir.join("warmup_welch.csv");
+    fs::write(&path, csv)?;
+    written.push(path);
+
+    if svg {
+        for (name, series) in names.iter().zip(welch) {
+            let points = series
+                .iter()
+                .enumerate()
+                .map(|(i, y)| (time(i), *y))
+                .collect::<Vec<(f64, f64)>>();
+            let top = series.iter().fold(0.0, |acc: f64, y| acc.max(*y));
+            let mut plot = SvgPlot::new(
+                &format!("Welch moving average of the {name}"),
+                "minutes",
+                name,
+                (0.0, time(series.len())),
+                (0.0, top * 1.2),
+            );
+            plot.line(&[(warmup, 0.0), (warmup, top * 1.2)], "#7f7f7f");
+            plot.line(&points, COLOURS[0]);
+            plot.legend(0, &format!("warm-up {warmup:.0}"), "#7f7f7f");
+            let path = dir.join(format!("warmup_{}.svg", column(name)));
+            fs::write(&path, plot.finish())?;
+            written.push(path);
+        }
+    }
+    Ok(written)
+}
+
+fn points_csv(header: &str, points: &[(f64, f64)]) -> String {
+    let mut csv = format!("{header}\n");
+    for (x, y) in points {
+        writeln!(csv, "{x:.6},{y:.6}").unwrap();
+    }
+    csv
+}
+
+struct Histogram {
+    start: f64,
+    width: f64,
+    counts: Vec<usize>,
+}
+
+impl Histogram {
+    fn new(sorted: &[f64]) -> Self {
+        // equal width bins from zero (service times can't be negative)
+        // with about sqrt(n) bins
+        let n = sorted.len();
+        let bins = ((n as f64).sqrt().ceil() as usize).max(1);
+        let start = sorted[0].min(0.0);
+        let width = match sorted[n - 1] > start {
+            true => (sorted[n - 1] - start) / bins as f64,
+            false => 1.0,
+        };
+        let mut counts = vec![0; bins];
+        for x in sorted {
+            let bin = ((x - start) / width).floor() as usize;
+            counts[bin.min(bins - 1)] += 1;
+        }
+        Histogram {
+            start,
+            width,
+            counts,
+        }
+    }
+
+    fn density(&self, count: usize) -> f64 {
+        count as f64 / (self.counts.iter().sum::<usize>() as f64 * self.width)
+    }
+
+    fn csv(&self, fits: &[Fit]) -> String {
+        let mut csv = String::from("bin_start,bin_end,count,density");
+        for fit in fits {
+            write!(csv, ",{}", fit.family.name().to_lowercase()).unwrap();
+        }
+        csv.push('\n');
+        for (i, count) in self.counts.iter().enumerate() {
+            let low = self.start + i as f64 * self.width;
+            let high = low + self.width;
+            write!(
+                csv,
+                "{low:.6},{high:.6},{count},{:.6}",
+                self.density(*count)
+            )
+            .unwrap();
+            for fit in fits {
+                // the density the fit predicts for the bin
+                write!(csv, ",{:.6}", (fit.cdf(high) - fit.cdf(low)) / self.width).unwrap();
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    fn svg(&self, stem: &str, fits: &[Fit]) -> String {
+        let end = self.start + self.width * self.counts.len() as f64;
+        let curves = fits
+            .iter()
+            .map(|fit| {
+                (0..=200)
+                    .map(|i| self.start + (end - self.start) * i as f64 / 200.0)
+                    .map(|x| (x, fit.pdf(x)))
+                    // the gamma and Weibull densities can be unbounded at 0
+                    .filter(|(_, y)| y.is_finite())
+                    .collect::<Vec<(f64, f64)>>()
+            })
+            .collect::<Vec<Vec<(f64, f64)>>>();
+
+        let top = self
+            .counts
+            .iter()
+            .map(|c| self.density(*c))
+            .fold(0.0, f64::max);
+        let mut plot = SvgPlot::new(
+            &format!("{stem} histogram"),
+            "minutes",
+            "density",
+            (self.start, end),
+            (0.0, top * 1.2),
+        );
+        for (i, count) in self.counts.iter().enumerate() {
+            let low = self.start + i as f64 * self.width;
+            plot.bar(low, low + self.width, self.density(*count));
+        }
+        for (i, (fit, curve)) in fits.iter().zip(curves.iter()).enumerate() {
+            let colour = COLOURS[i % COLOURS.len()];
+            // keep the curves inside the frame
+            let clipped = curve
+                .iter()
+                .map(|(x, y)| (*x, y.min(top * 1.2)))
+                .collect::<Vec<(f64, f64)>>();
+            plot.line(&clipped, colour);
+            plot.legend(i, fit.family.name(), colour);
+        }
+        plot.finish()
+    }
+}
+
+fn scatter_svg(title: &str, x_label: &str, y_label: &str, points: &[(f64, f64)]) -> String {
+    let range = points
+        .iter()
+        .flat_map(|(x, y)| [*x, *y])
+        .filter(|v| v.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), v| {
+            (low.min(v), high.max(v))
+        });
+    let mut plot = SvgPlot::new(title, x_label, y_label, range, range);
+    // a perfect fit lies on the diagonal
+    plot.line(&[(range.0, range.0), (range.1, range.1)], "#7f7f7f");
+    for (x, y) in points {
+        plot.point(*x, *y, COLOURS[0]);
+    }
+    plot.finish()
+}
+
+struct SvgPlot {
+    x_range: (f64, f64),
+    y_range: (f64, f64),
+    body: String,
+}
+
+impl SvgPlot {
+    const WIDTH: f64 = 640.0;
+    const HEIGHT: f64 = 480.0;
+    const MARGIN: f64 = 60.0;
+
+    fn new(title: &str, x_label: &str, y_label: &str, x: (f64, f64), y: (f64, f64)) -> Self {
+        // widen degenerate ranges so every point maps into the frame
+        let widen = |(low, high): (f64, f64)| match high > low {
+            true => (low, high),
+            false => (low - 0.5, low + 0.5),
+        };
+        let mut plot = SvgPlot {
+            x_range: widen(x),
+            y_range: widen(y),
+            body: String::new(),
+        };
+        let (w, h, m) = (Self::WIDTH, Self::HEIGHT, Self::MARGIN);
+        write!(
+            plot.body,
+            "<rect x=\"{m}\" y=\"{m}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>\n\
+            <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"16\">{}</text>\n\
+            <text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n\
+            <text x=\"15\" y=\"{}\" text-anchor=\"middle\" transform=\"rotate(-90 15 {})\">{}</text>\n",
+            w - 2.0 * m,
+            h - 2.0 * m,
+            w / 2.0,
+            m / 2.0,
+            escape(title),
+            w / 2.0,
+            h - 15.0,
+            escape(x_label),
+            h / 2.0,
+            h / 2.0,
+            escape(y_label),
+        )
+        .unwrap();
+        for i in 0..=4 {
+            let fraction = i as f64 / 4.0;
+            let x_value = plot.x_range.0 + fraction * (plot.x_range.1 - plot.x_range.0);
+            let y_value = plot.y_range.0 + fraction * (plot.y_range.1 - plot.y_range.0);
+            let (x, _) = plot.to_px(x_value, plot.y_range.0);
+            let (_, y) = plot.to_px(plot.x_range.0, y_value);
+            writeln!(
+                plot.body,
+                "<text x=\"{x:.1}\" y=\"{}\" text-anchor=\"middle\" font-size=\"11\">{}</text>\n\
+                <text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\" font-size=\"11\">{}</text>",
+                h - m + 15.0,
+                tick_label(x_value),
+                m - 5.0,
+                y + 4.0,
+                tick_label(y_value),
+            )
+            .unwrap();
+        }
+        plot
+    }
+
+    fn to_px(&self, x: f64, y: f64) -> (f64, f64) {
+        let inner_w = Self::WIDTH - 2.0 * Self::MARGIN;
+        let inner_h = Self::HEIGHT - 2.0 * Self::MARGIN;
+        (
+            Self::MARGIN + (x - self.x_range.0) / (self.x_range.1 - self.x_range.0) * inner_w,
+            Self::HEIGHT
+                - Self::MARGIN
+                - (y - self.y_range.0) / (self.y_range.1 - self.y_range.0) * inner_h,
+        )
+    }
+
+    fn bar(&mut self, low: f64, high: f64, height: f64) {
+        let (x0, y0) = self.to_px(low, height);
+        let (x1, y1) = self.to_px(high, self.y_range.0);
+        writeln!(
+            self.body,
+            "<rect x=\"{x0:.1}\" y=\"{y0:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+            fill=\"#aec7e8\" stroke=\"#1f77b4\"/>",
+            x1 - x0,
+            y1 - y0
+        )
+        .unwrap();
+    }
+
+    fn line(&mut self, points: &[(f64, f64)], colour: &str) {
+        let points = points
+            .iter()
+            .map(|(x, y)| {
+                let (x, y) = self.to_px(*x, *y);
+                format!("{x:.1},{y:.1}")
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        writeln!(
+            self.body,
+            "<polyline points=\"{points}\" fill=\"none\" stroke=\"{colour}\" stroke-width=\"1.5\"/>"
+        )
+        .unwrap();
+    }
+
+    fn point(&mut self, x: f64, y: f64, colour: &str) {
+        if !x.is_finite() || !y.is_finite() {
+            return;
+        }
+        let (x, y) = self.to_px(x, y);
+        writeln!(
+            self.body,
+            "<circle cx=\"{x:.1}\" cy=\"{y:.1}\" r=\"2\" fill=\"{colour}\"/>"
+        )
+        .unwrap();
+    }
+
+    fn legend(&mut self, i: usize, label: &str, colour: &str) {
+        let x = Self::WIDTH - Self::MARGIN - 110.0;
+        let y = Self::MARGIN + 20.0 + 16.0 * i as f64;
+        writeln!(
+            self.body,
+            "<line x1=\"{x}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{colour}\" stroke-width=\"2\"/>\n\
+            <text x=\"{}\" y=\"{y}\" font-size=\"12\">{}</text>",
+            y - 4.0,
+            x + 20.0,
+            y - 4.0,
+            x + 25.0,
+            escape(label)
+        )
+        .unwrap();
+    }
+
+    fn finish(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+            font-family=\"sans-serif\">\n\
+            <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n{}</svg>\n",
+            Self::WIDTH,
+            Self::HEIGHT,
+            self.body
+        )
+    }
+}
+
+fn tick_label(v: f64) -> String {
+    match v.abs() >= 100.0 || v == v.round() {
+        true => format!("{v:.0}"),
+        false => format!("{v:.2}"),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
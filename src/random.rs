@@ -1,29 +1,209 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::checkpoint::{Checkpoint, CheckpointError, Reader, Writer};
+use crate::generators::{Mrg32k3a, Pcg64, Xoshiro256StarStar};
 
+// a uniform random number generator which can jump ahead. the period
+// of every generator is cut into streams and every stream into
+// substreams, in the style of L'Ecuyer's RngStream
+pub trait RandomSource {
+    // a uniform on [0, 1)
+    fn float(&mut self) -> f64;
+
+    fn boolean(&mut self) -> bool {
+        // the top bit. the low bits of an lcg with a power of two
+        // modulus have short periods, i.e the lowest just alternates
+        self.float() < 0.5
+    }
+
+    // jump to the start of the next substream or stream
+    fn jump_substream(&mut self);
+    fn jump_stream(&mut self);
+
+    // the number of substreams in one stream
+    fn substreams(&self) -> u64 {
+        u64::MAX
+    }
+
+    fn boxed_clone(&self) -> Box<dyn RandomSource>;
+
+    // the generator and its full state, for checkpoints
+    fn generator(&self) -> Generator;
+    fn state(&self) -> Vec<u64>;
+}
+
+choices! {
+    pub enum Generator: "generator" {
+        Lcg => "lcg",
+        Mrg32k3a => "mrg32k3a",
+        Pcg64 => "pcg64",
+        Xoshiro256StarStar => "xoshiro256**",
+    }
+}
+
+impl Generator {
+    pub fn seeded(&self, seed: u32) -> Box<dyn RandomSource> {
+        match self {
+            Self::Lcg => Box::new(LcmGenerator::with_seed(seed)),
+            Self::Mrg32k3a => Box::new(Mrg32k3a::with_seed(seed)),
+            Self::Pcg64 => Box::new(Pcg64::with_seed(seed)),
+            Self::Xoshiro256StarStar => Box::new(Xoshiro256StarStar::with_seed(seed)),
+        }
+    }
+
+    // the substreams in each stream, which bounds the replications
+    // of a run as each draws from its own
+    pub fn substreams(&self) -> u64 {
+        // the same whatever the seed
+        self.seeded(0).substreams()
+    }
+
+    pub fn restore(&self, state: &[u64]) -> Option<Box<dyn RandomSource>> {
+        // None if the state is not one the generator can be in
+        match self {
+            Self::Lcg => LcmGenerator::from_state(state).map(|g| Box::new(g) as _),
+            Self::Mrg32k3a => Mrg32k3a::from_state(state).map(|g| Box::new(g) as _),
+            Self::Pcg64 => Pcg64::from_state(state).map(|g| Box::new(g) as _),
+            Self::Xoshiro256StarStar => {
+                Xoshiro256StarStar::from_state(state).map(|g| Box::new(g) as _)
+            }
+        }
+    }
+}
+
+pub fn splitmix64(state: &mut u64) -> u64 {
+    // expands a small seed into well mixed words
+    // for the generators with large states
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// one stream of a generator. each source of randomness in the model
+// owns a stream and each replication a substream, so one element
+// drawing more or fewer numbers never shifts the draws of another
 pub struct Random {
-    gen: LcmGenerator,
+    source: Box<dyn RandomSource>,
+    // the state at the start of the current substream
+    substream_start: Box<dyn RandomSource>,
+    // draws 1 - u in place of u, for antithetic replications
+    antithetic: bool,
 }
 
 impl Random {
-    pub fn new() -> Self {
+    pub fn streams(generator: Generator, seed: u32, count: usize) -> Vec<Random> {
+        // successive, non-overlapping streams of one generator
+        let mut next = generator.seeded(seed);
+        (0..count)
+            .map(|_| {
+                let stream = Random {
+                    source: next.boxed_clone(),
+                    substream_start: next.boxed_clone(),
+                    antithetic: false,
+                };
+                next.jump_stream();
+                stream
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    pub fn from_source(source: Box<dyn RandomSource>) -> Random {
         Random {
-            gen: LcmGenerator::new(),
+            substream_start: source.boxed_clone(),
+            source,
+            antithetic: false,
         }
     }
 
-    pub fn with_seed(seed: u32) -> Self {
-        Self { gen: LcmGenerator::with_seed(seed) }
+    pub fn advance_substreams(&mut self, count: u64) -> std::result::Result<(), String> {
+        // moves to the start of the substream count after the current one
+        let substreams = self.source.substreams();
+        if count >= substreams {
+            return Err(format!(
+                "a {} stream has {substreams} substreams, too few for substream {count}",
+                self.source.generator()
+            ));
+        }
+        let mut source = self.substream_start.boxed_clone();
+        for _ in 0..count {
+            source.jump_substream();
+        }
+        self.substream_start = source.boxed_clone();
+        self.source = source;
+        Ok(())
+    }
+
+    pub fn set_antithetic(&mut self, antithetic: bool) {
+        self.antithetic = antithetic;
     }
 
     pub fn boolean(&mut self) -> bool {
-        self.gen.set_next() % 2 == 0
+        // a boolean is u < 0.5, so the antithetic one is its negation
+        self.source.boolean() != self.antithetic
     }
 
     pub fn float(&mut self) -> f64 {
-        self.gen.set_next() as f64 / self.gen.m as f64
+        let u = self.source.float();
+        // 1 - u, except that 0 stays 0 to keep the result on [0, 1)
+        match self.antithetic && u > 0.0 {
+            true => 1.0 - u,
+            false => u,
+        }
     }
 }
 
+pub fn lcg_parameters(seed: u32) -> (u64, u64) {
+    // the multiplier and modulus, for the spectral test
+    let gen = LcmGenerator::with_seed(seed);
+    (gen.a as u64, gen.m)
+}
+
+impl Checkpoint for Random {
+    // the generator with the current state, the state at
+    // the start of the substream and whether it is antithetic
+    fn save(&self, out: &mut Writer) {
+        out.line("random").word(self.source.generator().name());
+        for source in [&self.source, &self.substream_start] {
+            let state = source.state();
+            out.value(state.len());
+            for word in state {
+                out.value(word);
+            }
+        }
+        out.value(self.antithetic);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> std::result::Result<(), CheckpointError> {
+        input.expect("random")?;
+        let generator = input
+            .word()?
+            .parse::<Generator>()
+            .map_err(|e| input.error(e))?;
+        let mut read_source = || {
+            let state = (0..input.value::<usize>()?)
+                .map(|_| input.value::<u64>())
+                .collect::<std::result::Result<Vec<u64>, _>>()?;
+            generator
+                .restore(&state)
+                .ok_or_else(|| input.error(format!("{state:?} is not a state of {generator}")))
+        };
+        let source = read_source()?;
+        let substream_start = read_source()?;
+        self.source = source;
+        self.substream_start = substream_start;
+        self.antithetic = input.value::<bool>()?;
+        Ok(())
+    }
+}
+
+// the original generator of the simulation. with a period of 2^40
+// its streams are 2^32 numbers long, giving 256 streams of 1024
+// substreams, each of which is 2^22 (~4 million) numbers long
+const SUBSTREAM_BITS: u32 = 22;
+const STREAM_BITS: u32 = 32;
+
+#[derive(Clone)]
 struct LcmGenerator {
     a: u32,     // multiplier
     c: u64,     // increment
@@ -31,6 +211,36 @@ struct LcmGenerator {
     x: u64,
 }
 
+impl RandomSource for LcmGenerator {
+    fn float(&mut self) -> f64 {
+        self.set_next() as f64 / self.m as f64
+    }
+
+    fn jump_substream(&mut self) {
+        self.advance(1 << SUBSTREAM_BITS);
+    }
+
+    fn jump_stream(&mut self) {
+        self.advance(1 << STREAM_BITS);
+    }
+
+    fn substreams(&self) -> u64 {
+        1 << (STREAM_BITS - SUBSTREAM_BITS)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+
+    fn generator(&self) -> Generator {
+        Generator::Lcg
+    }
+
+    fn state(&self) -> Vec<u64> {
+        vec![self.a as u64, self.c, self.m, self.x]
+    }
+}
+
 impl LcmGenerator {
     const INIT_SEED: u32 = 11774353;
     const BIG_PRIME: u64 = 999999000001;
@@ -42,16 +252,6 @@ impl LcmGenerator {
     // (default 25) is tuned experimentally
     // hard to find a good number for larger primes
 
-    pub fn new() -> Self {
-        let seed: u32 = match SystemTime::now().duration_since(UNIX_EPOCH) {
-            Ok(dur) => dur.subsec_nanos().reverse_bits(),
-            Err(_) => {
-                panic!("SystemTime before UNIX EPOCH!");
-            }
-        };
-        Self::with_seed(seed)
-    }
-
     pub fn with_seed(seed: u32) -> Self {
         log!("seed:  {seed}");
         let seed = Self::INIT_SEED as u64 + seed as u64;
@@ -62,7 +262,7 @@ impl LcmGenerator {
         let mut c = seed << 25;
         while c < Self::INIT_SEED as u64 || !relatively_prime(m, c) {
             c = Self::next(
-                c + c >> 5,
+                (c + c) >> 5,
                 (4 * ((seed >> 7) + 1) + 1) as u32,
                 0,
                 Self::BIG_PRIME,
@@ -71,17 +271,17 @@ impl LcmGenerator {
         }
 
         let mut a = Self::next(seed + 1, Self::INIT_SEED, c, 2u64.pow(24)) as u32;
-        while a < Self::INIT_SEED || !(gcd(a as u64, 4) == 4) {
+        while a < Self::INIT_SEED || gcd(a as u64, 4) != 4 {
             a = Self::next(a as u64, Self::INIT_SEED, c, 2u64.pow(24)) as u32;
             log!("a:  {a}");
         }
         a += 1; // since gcd(a, 4) == 4 earlier, a = 4k+1
-        assert!((a - 1) % 4 == 0);
+        assert!((a - 1).is_multiple_of(4));
         log!("a:  {a}");
 
         LcmGenerator {
             a, // <= 2^24 - 1
-            x: Self::next((seed << 2) as u64, a, c, m),
+            x: Self::next(seed << 2, a, c, m),
             c, // <= Self::BIG_PRIME - 1
             m, // <= 2^40
         }
@@ -92,6 +292,38 @@ impl LcmGenerator {
         self.x
     }
 
+    pub fn advance(&mut self, steps: u64) {
+        // jumps ahead as if set_next was called steps times. x -> a x + c
+        // composed with itself is x -> a^2 x + c (a + 1), so the jump
+        // takes log2(steps) squarings
+        let m = self.m as u128;
+        let (mut a, mut c) = (self.a as u128, self.c as u128);
+        let (mut mul, mut add) = (1u128, 0u128);
+        let mut steps = steps;
+        while steps > 0 {
+            if steps & 1 == 1 {
+                mul = mul * a % m;
+                add = (add * a + c) % m;
+            }
+            c = c * (a + 1) % m;
+            a = a * a % m;
+            steps >>= 1;
+        }
+        self.x = ((mul * self.x as u128 + add) % m) as u64;
+    }
+
+    fn from_state(state: &[u64]) -> Option<Self> {
+        match *state {
+            [a, c, m, x] if a <= u32::MAX as u64 && m > 0 && c < m && x < m => Some(LcmGenerator {
+                a: a as u32,
+                c,
+                m,
+                x,
+            }),
+            _ => None,
+        }
+    }
+
     fn next(x: u64, a: u32, c: u64, m: u64) -> u64 {
         (a as u64).wrapping_mul(x).wrapping_add(c).wrapping_rem(m)
     }
@@ -108,5 +340,43 @@ fn gcd(mut a: u64, mut b: u64) -> u64 {
 }
 
 fn relatively_prime(a: u64, b: u64) -> bool {
-    return gcd(a, b) == 1;
+    gcd(a, b) == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lcg_has_a_full_period() {
+        // there are no published outputs for this generator, so check
+        // the Hull-Dobell conditions for a full period of m = 2^40
+        for seed in [1, 2, 12345] {
+            let gen = LcmGenerator::with_seed(seed);
+            assert!(relatively_prime(gen.m, gen.c));
+            assert!((gen.a - 1).is_multiple_of(4));
+        }
+    }
+
+    #[test]
+    fn substreams_end_with_the_stream() {
+        let substreams = Generator::Lcg.substreams();
+        assert_eq!(substreams, 1024);
+        let mut stream = Random::streams(Generator::Lcg, 1, 1).remove(0);
+        assert!(stream.advance_substreams(substreams - 1).is_ok());
+        assert!(stream.advance_substreams(substreams).is_err());
+    }
+
+    #[test]
+    fn lcg_jumps_agree_with_stepping() {
+        for seed in [1, 2, 12345] {
+            let mut gen = LcmGenerator::with_seed(seed);
+            let mut jumped = gen.clone();
+            jumped.advance(1000);
+            (0..1000).for_each(|_| {
+                gen.set_next();
+            });
+            assert_eq!(jumped.x, gen.x);
+        }
+    }
 }
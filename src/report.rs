@@ -0,0 +1,28 @@
+use std::fmt::Display;
+
+// how a command prints its results: as aligned tables for reading, or
+// as comma separated values with a header row for other programs. a
+// CSV field holding a comma, quote or newline is quoted, doubling any
+// quotes inside it
+
+choices! {
+    pub enum Format: "format" {
+        Text => "text",
+        Csv => "csv",
+    }
+}
+
+// one row of comma separated values
+pub fn csv<T: Display>(fields: impl IntoIterator<Item = T>) -> String {
+    fields
+        .into_iter()
+        .map(|field| {
+            let field = field.to_string();
+            match field.contains([',', '"', '\n']) {
+                true => format!("\"{}\"", field.replace('"', "\"\"")),
+                false => field,
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
@@ -0,0 +1,134 @@
+use crate::statistics::ConfidenceInterval;
+use crate::topology::Topology;
+
+// the measures of a replication by name, so code which only needs
+// some of them, or all of them in turn, does not depend on their order.
+// how many of each there are depends on the facility's topology
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplicationResult {
+    // the time average number of components in each of Topology::buffers
+    pub occupancy: Vec<f64>,
+    // the fraction of the time each workstation was assembling
+    pub utilization: Vec<f64>,
+    // the number of each product finished per minute
+    pub throughput: Vec<f64>,
+    // the fraction of the time each inspector was blocked
+    pub blocking: Vec<f64>,
+    // the time average number of components in the system
+    pub wip: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Measure {
+    // the index into the field of ReplicationResult
+    Occupancy(usize),
+    Utilization(usize),
+    Throughput(usize),
+    Blocking(usize),
+    Wip,
+}
+
+impl Measure {
+    pub fn all(topology: &Topology) -> Vec<Measure> {
+        let result = ReplicationResult::new(topology);
+        (0..result.occupancy.len())
+            .map(Measure::Occupancy)
+            .chain((0..result.utilization.len()).map(Measure::Utilization))
+            .chain((0..result.throughput.len()).map(Measure::Throughput))
+            .chain((0..result.blocking.len()).map(Measure::Blocking))
+            .chain([Measure::Wip])
+            .collect()
+    }
+
+    pub fn name(&self, topology: &Topology) -> String {
+        match self {
+            Self::Occupancy(i) => format!("{} occupancy", topology.buffer_names()[*i]),
+            Self::Utilization(i) => format!("{} busy ratio", topology.workstations[*i].name),
+            Self::Throughput(i) => format!("{} throughput", topology.products[*i].name),
+            Self::Blocking(i) => format!("{} blocked ratio", topology.inspectors[*i].name),
+            Self::Wip => "Total occupancy".to_string(),
+        }
+    }
+
+    // a fraction of the time, which may be near 0
+    pub fn is_ratio(&self) -> bool {
+        matches!(self, Self::Utilization(_) | Self::Blocking(_))
+    }
+}
+
+impl ReplicationResult {
+    // every measure of the topology at 0
+    pub fn new(topology: &Topology) -> Self {
+        ReplicationResult {
+            occupancy: vec![0.0; topology.buffers().len()],
+            utilization: vec![0.0; topology.workstations.len()],
+            throughput: vec![0.0; topology.products.len()],
+            blocking: vec![0.0; topology.inspectors.len()],
+            wip: 0.0,
+        }
+    }
+
+    pub fn get(&self, measure: Measure) -> f64 {
+        match measure {
+            Measure::Occupancy(i) => self.occupancy[i],
+            Measure::Utilization(i) => self.utilization[i],
+            Measure::Throughput(i) => self.throughput[i],
+            Measure::Blocking(i) => self.blocking[i],
+            Measure::Wip => self.wip,
+        }
+    }
+
+    pub fn set(&mut self, measure: Measure, value: f64) {
+        match measure {
+            Measure::Occupancy(i) => self.occupancy[i] = value,
+            Measure::Utilization(i) => self.utilization[i] = value,
+            Measure::Throughput(i) => self.throughput[i] = value,
+            Measure::Blocking(i) => self.blocking[i] = value,
+            Measure::Wip => self.wip = value,
+        }
+    }
+
+    // the rate at which components leave the system in products, each
+    // product taking the components of its recipe, averaged over the types
+    pub fn total_throughput(&self, topology: &Topology) -> f64 {
+        let components = self
+            .throughput
+            .iter()
+            .zip(topology.products.iter())
+            .fold(0.0, |acc, (t, p)| acc + t * p.recipe.len() as f64);
+        components / self.throughput.len() as f64
+    }
+}
+
+#[derive(Default)]
+pub struct ResultsAggregator {
+    results: Vec<ReplicationResult>,
+}
+
+impl ResultsAggregator {
+    pub fn push(&mut self, result: ReplicationResult) {
+        self.results.push(result);
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    // the observations of one measure, in the order they were added
+    pub fn values(&self, measure: Measure) -> Vec<f64> {
+        self.results.iter().map(|r| r.get(measure)).collect()
+    }
+
+    pub fn interval(&self, measure: Measure, confidence: f64) -> Option<ConfidenceInterval> {
+        ConfidenceInterval::from_sample(&self.values(measure), confidence)
+    }
+}
+
+impl FromIterator<ReplicationResult> for ResultsAggregator {
+    fn from_iter<I: IntoIterator<Item = ReplicationResult>>(iter: I) -> Self {
+        ResultsAggregator {
+            results: iter.into_iter().collect(),
+        }
+    }
+}
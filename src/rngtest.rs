@@ -0,0 +1,408 @@
+use std::f64::consts::PI;
+
+use crate::gof::TestResult;
+use crate::random::Random;
+use crate::special::{chi_square_survival, ln_gamma, normal_cdf};
+
+// empirical tests of a uniform generator, after Knuth's TAOCP
+// vol. 2 section 3.3.2. each test draws its own numbers and reports
+// a p-value, so the whole battery passes or fails at one level
+
+pub struct Outcome {
+    pub name: String,
+    pub result: TestResult,
+}
+
+fn chi_square_counts(observed: &[usize], probabilities: &[f64]) -> TestResult {
+    let n = observed.iter().sum::<usize>() as f64;
+    let statistic = observed
+        .iter()
+        .zip(probabilities)
+        .map(|(o, p)| (*o as f64 - n * p).powi(2) / (n * p))
+        .sum();
+    TestResult {
+        statistic,
+        p_value: chi_square_survival(statistic, observed.len() - 1),
+    }
+}
+
+fn two_sided(z: f64) -> TestResult {
+    // for a statistic which is standard normal under the null hypothesis
+    TestResult {
+        statistic: z,
+        p_value: 2.0 * normal_cdf(-z.abs()),
+    }
+}
+
+pub fn frequency(rand: &mut Random, n: usize, bins: usize) -> TestResult {
+    let mut observed = vec![0; bins];
+    for _ in 0..n {
+        observed[((rand.float() * bins as f64) as usize).min(bins - 1)] += 1;
+    }
+    chi_square_counts(&observed, &vec![1.0 / bins as f64; bins])
+}
+
+pub fn serial(rand: &mut Random, n: usize, dimension: u32, divisions: usize) -> TestResult {
+    // non-overlapping tuples of numbers should fall evenly
+    // into the divisions^dimension cells of the unit cube
+    let cells = divisions.pow(dimension);
+    let mut observed = vec![0; cells];
+    for _ in 0..n / dimension as usize {
+        let cell = (0..dimension).fold(0, |cell, _| {
+            cell * divisions + ((rand.float() * divisions as f64) as usize).min(divisions - 1)
+        });
+        observed[cell] += 1;
+    }
+    chi_square_counts(&observed, &vec![1.0 / cells as f64; cells])
+}
+
+pub fn runs_up_down(rand: &mut Random, n: usize) -> TestResult {
+    // the number of runs a in n numbers has mean (2n - 1) / 3
+    // and variance (16n - 29) / 90
+    let mut previous = rand.float();
+    let mut ascending = None;
+    let mut runs = 0;
+    for _ in 1..n {
+        let u = rand.float();
+        let up = u > previous;
+        if ascending != Some(up) {
+            runs += 1;
+            ascending = Some(up);
+        }
+        previous = u;
+    }
+    let n = n as f64;
+    two_sided((runs as f64 - (2.0 * n - 1.0) / 3.0) / ((16.0 * n - 29.0) / 90.0).sqrt())
+}
+
+pub fn gap(rand: &mut Random, n: usize, low: f64, high: f64, longest: usize) -> TestResult {
+    // the lengths of the gaps between numbers falling in [low, high)
+    // are geometric, with every gap of longest or more in one cell
+    let p = high - low;
+    let mut observed = vec![0; longest + 1];
+    let mut length = 0;
+    for _ in 0..n {
+        let u = rand.float();
+        match low <= u && u < high {
+            true => {
+                observed[length.min(longest)] += 1;
+                length = 0;
+            }
+            false => length += 1,
+        }
+    }
+    let probabilities = (0..=longest)
+        .map(|r| match r < longest {
+            true => p * (1.0 - p).powi(r as i32),
+            false => (1.0 - p).powi(longest as i32),
+        })
+        .collect::<Vec<f64>>();
+    chi_square_counts(&observed, &probabilities)
+}
+
+pub fn poker(rand: &mut Random, n: usize) -> TestResult {
+    // hands of five decimal digits, classified by the number of distinct
+    // digits r with probability 10!/(10 - r)! S(5, r) / 10^5. hands with
+    // one or two distinct digits are too rare to test apart
+    const STIRLING: [f64; 6] = [0.0, 1.0, 15.0, 25.0, 10.0, 1.0];
+    let probability =
+        |r: usize| (0..r).map(|i| (10 - i) as f64).product::<f64>() * STIRLING[r] / 1e5;
+    let mut observed = vec![0; 4];
+    for _ in 0..n / 5 {
+        let mut seen = [false; 10];
+        for _ in 0..5 {
+            seen[((rand.float() * 10.0) as usize).min(9)] = true;
+        }
+        let distinct = seen.iter().filter(|s| **s).count();
+        observed[distinct.max(2) - 2] += 1;
+    }
+    let probabilities = [
+        probability(1) + probability(2),
+        probability(3),
+        probability(4),
+        probability(5),
+    ];
+    chi_square_counts(&observed, &probabilities)
+}
+
+pub fn autocorrelation(rand: &mut Random, n: usize, lag: usize) -> TestResult {
+    // the sample autocorrelation at a lag is about N(0, 1 / n)
+    let u = (0..n).map(|_| rand.float()).collect::<Vec<f64>>();
+    let mean = u.iter().sum::<f64>() / n as f64;
+    let variance = u.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+    let covariance = u
+        .iter()
+        .zip(u.iter().skip(lag))
+        .map(|(x, y)| (x - mean) * (y - mean))
+        .sum::<f64>();
+    two_sided(covariance / variance * (n as f64).sqrt())
+}
+
+pub fn bit_frequency(rand: &mut Random, n: usize) -> TestResult {
+    let ones = (0..n).filter(|_| rand.boolean()).count();
+    chi_square_counts(&[n - ones, ones], &[0.5, 0.5])
+}
+
+pub fn bit_serial(rand: &mut Random, n: usize) -> TestResult {
+    // successive booleans should be independent, which a
+    // generator whose low bit alternates badly fails
+    let mut observed = vec![0; 4];
+    for _ in 0..n / 2 {
+        observed[2 * rand.boolean() as usize + rand.boolean() as usize] += 1;
+    }
+    chi_square_counts(&observed, &[0.25; 4])
+}
+
+pub fn battery(rand: &mut Random, n: usize, lags: &[usize]) -> Vec<Outcome> {
+    let mut outcomes = vec![
+        ("frequency (100 bins)".to_string(), frequency(rand, n, 100)),
+        ("serial pairs (10 x 10)".to_string(), serial(rand, n, 2, 10)),
+        ("serial triples (10^3)".to_string(), serial(rand, n, 3, 10)),
+        ("runs up and down".to_string(), runs_up_down(rand, n)),
+        ("gap [0, 0.5)".to_string(), gap(rand, n, 0.0, 0.5, 10)),
+        ("poker (5 digits)".to_string(), poker(rand, n)),
+    ];
+    for lag in lags {
+        outcomes.push((
+            format!("autocorrelation lag {lag}"),
+            autocorrelation(rand, n, *lag),
+        ));
+    }
+    outcomes.push(("boolean frequency".to_string(), bit_frequency(rand, n)));
+    outcomes.push(("boolean serial pairs".to_string(), bit_serial(rand, n)));
+    outcomes
+        .into_iter()
+        .map(|(name, result)| Outcome { name, result })
+        .collect()
+}
+
+// Knuth's spectral test of an lcg with multiplier a and modulus m.
+// in t dimensions the points (x_n, .., x_n+t-1) / m lie on parallel
+// hyperplanes at most 1 / nu_t apart, where nu_t is the length of the
+// shortest non-zero s with s_1 + s_2 a + .. + s_t a^(t-1) = 0 (mod m)
+pub struct Spectral {
+    pub dimension: usize,
+    pub nu: f64,
+    // Knuth's figure of merit mu_t, the volume of the ellipsoid
+    // given by nu_t relative to m. 0.1 or more passes
+    pub merit: f64,
+}
+
+impl Spectral {
+    pub fn accepts(&self) -> bool {
+        self.merit >= 0.1
+    }
+}
+
+pub fn spectral(a: u64, m: u64, max_dimension: usize) -> Vec<Spectral> {
+    (2..=max_dimension)
+        .map(|t| {
+            // the rows (m, 0, ..), (-a, 1, 0, ..), (-a^2, 0, 1, ..), ..
+            // are a basis of the dual lattice
+            let mut power = 1u128;
+            let basis = (0..t)
+                .map(|i| {
+                    let mut row = vec![0i128; t];
+                    match i {
+                        0 => row[0] = m as i128,
+                        _ => {
+                            power = power * a as u128 % m as u128;
+                            row[0] = -(power as i128);
+                            row[i] = 1;
+                        }
+                    }
+                    row
+                })
+                .collect::<Vec<Vec<i128>>>();
+            let nu = (shortest_vector(basis) as f64).sqrt();
+            let t_f = t as f64;
+            let ln_merit =
+                t_f / 2.0 * PI.ln() + t_f * nu.ln() - ln_gamma(t_f / 2.0 + 1.0) - (m as f64).ln();
+            Spectral {
+                dimension: t,
+                nu,
+                merit: ln_merit.exp(),
+            }
+        })
+        .collect()
+}
+
+fn dot(a: &[i128], b: &[i128]) -> i128 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn gram_schmidt(basis: &[Vec<i128>]) -> (Vec<Vec<f64>>, Vec<f64>) {
+    // the coefficients mu[i][j] and the squared lengths
+    // of the orthogonalised vectors
+    let n = basis.len();
+    let mut orthogonal: Vec<Vec<f64>> = Vec::with_capacity(n);
+    let mut mu = vec![vec![0.0; n]; n];
+    let mut lengths = vec![0.0; n];
+    for i in 0..n {
+        let mut v = basis[i].iter().map(|x| *x as f64).collect::<Vec<f64>>();
+        for j in 0..i {
+            let projection = basis[i]
+                .iter()
+                .zip(orthogonal[j].iter())
+                .map(|(x, y)| *x as f64 * y)
+                .sum::<f64>();
+            mu[i][j] = projection / lengths[j];
+            for (v, o) in v.iter_mut().zip(orthogonal[j].iter()) {
+                *v -= mu[i][j] * o;
+            }
+        }
+        lengths[i] = v.iter().map(|x| x * x).sum();
+        orthogonal.push(v);
+    }
+    (mu, lengths)
+}
+
+fn lll(basis: &mut [Vec<i128>]) {
+    // Lenstra-Lenstra-Lovasz reduction with delta = 3/4. the
+    // dimensions are tiny so the orthogonalisation is just redone
+    let mut k = 1;
+    while k < basis.len() {
+        for j in (0..k).rev() {
+            let q = gram_schmidt(basis).0[k][j].round() as i128;
+            if q != 0 {
+                let row = basis[j].clone();
+                for (x, y) in basis[k].iter_mut().zip(row) {
+                    *x -= q * y;
+                }
+            }
+        }
+        let (mu, lengths) = gram_schmidt(basis);
+        match lengths[k] >= (0.75 - mu[k][k - 1].powi(2)) * lengths[k - 1] {
+            true => k += 1,
+            false => {
+                basis.swap(k, k - 1);
+                k = (k - 1).max(1);
+            }
+        }
+    }
+}
+
+fn shortest_vector(mut basis: Vec<Vec<i128>>) -> i128 {
+    // the squared length of the shortest non-zero lattice vector,
+    // found by enumerating every combination of the reduced basis
+    // within the best length so far (Fincke-Pohst)
+    lll(&mut basis);
+    let (mu, lengths) = gram_schmidt(&basis);
+    let mut best = basis.iter().map(|b| dot(b, b)).min().unwrap();
+    let mut x = vec![0i128; basis.len()];
+    enumerate(
+        &basis,
+        &mu,
+        &lengths,
+        basis.len() - 1,
+        0.0,
+        &mut x,
+        &mut best,
+    );
+    best
+}
+
+fn enumerate(
+    basis: &[Vec<i128>],
+    mu: &[Vec<f64>],
+    lengths: &[f64],
+    level: usize,
+    partial: f64,
+    x: &mut [i128],
+    best: &mut i128,
+) {
+    let center = -(level + 1..basis.len())
+        .map(|j| x[j] as f64 * mu[j][level])
+        .sum::<f64>();
+    // a little slack for the rounding in the floating point bound
+    let room = *best as f64 * (1.0 + 1e-9) - partial;
+    if room < 0.0 {
+        return;
+    }
+    let radius = (room / lengths[level]).sqrt();
+    for xi in (center - radius).ceil() as i128..=(center + radius).floor() as i128 {
+        x[level] = xi;
+        let partial = partial + (xi as f64 - center).powi(2) * lengths[level];
+        if level > 0 {
+            enumerate(basis, mu, lengths, level - 1, partial, x, best);
+        } else if x.iter().any(|xi| *xi != 0) {
+            let v = (0..basis[0].len())
+                .map(|c| x.iter().zip(basis).map(|(xi, b)| xi * b[c]).sum())
+                .collect::<Vec<i128>>();
+            *best = (*best).min(dot(&v, &v));
+        }
+    }
+    x[level] = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{Generator, RandomSource};
+
+    // IBM's RANDU, x' = 65539 x mod 2^31, whose triples famously
+    // lie on the 15 planes 9x - 6y + z = k
+    #[derive(Clone)]
+    struct Randu(u64);
+
+    impl RandomSource for Randu {
+        fn float(&mut self) -> f64 {
+            self.0 = self.0 * 65539 % (1 << 31);
+            self.0 as f64 / (1u64 << 31) as f64
+        }
+
+        fn jump_substream(&mut self) {
+            unimplemented!()
+        }
+
+        fn jump_stream(&mut self) {
+            unimplemented!()
+        }
+
+        fn boxed_clone(&self) -> Box<dyn RandomSource> {
+            Box::new(self.clone())
+        }
+
+        fn generator(&self) -> Generator {
+            unimplemented!()
+        }
+
+        fn state(&self) -> Vec<u64> {
+            vec![self.0]
+        }
+    }
+
+    fn squared(spectral: &[Spectral]) -> Vec<i64> {
+        spectral
+            .iter()
+            .map(|s| s.nu.powi(2).round() as i64)
+            .collect()
+    }
+
+    #[test]
+    fn spectral_matches_knuths_table() {
+        // Lehmer's original multiplier 23 modulo 10^8 + 1, tabulated
+        // in TAOCP 3.3.4, has nu_t^2 = 530 for t = 2, 3, 4
+        let spectral = spectral(23, 100_000_001, 4);
+        assert_eq!(squared(&spectral), [530, 530, 530]);
+        assert!(spectral.iter().all(|s| !s.accepts()));
+    }
+
+    #[test]
+    fn randu_fails_the_spectral_test_in_three_dimensions() {
+        let spectral = spectral(65539, 1 << 31, 4);
+        assert_eq!(squared(&spectral[1..]), [118, 116]);
+        assert!(spectral[0].accepts() && !spectral[1].accepts());
+    }
+
+    #[test]
+    fn randu_fails_the_serial_test_of_triples() {
+        let mut randu = Random::from_source(Box::new(Randu(1)));
+        assert!(frequency(&mut randu, 300_000, 100).accepts(0.001));
+        assert!(serial(&mut randu, 300_000, 2, 20).accepts(0.001));
+        assert!(!serial(&mut randu, 300_000, 3, 20).accepts(0.001));
+
+        let mut lcg = Random::streams(Generator::Lcg, 1, 1).remove(0);
+        assert!(serial(&mut lcg, 300_000, 3, 20).accepts(0.001));
+    }
+}
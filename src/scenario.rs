@@ -0,0 +1,680 @@
+use std::fmt::{Display, Formatter, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::distribution;
+use crate::fitting::Family;
+use crate::inspector::Routing;
+use crate::random::Generator;
+use crate::results::Measure;
+use crate::statistics::{Precision, Target};
+use crate::topology::{FacilityError, Topology};
+use crate::{CAPACITY, COMPONENT_COUNT, INIT_R, MAX_R, SEED, WARMUP};
+
+// everything a run of the model depends on: the facility, the
+// distributions of its service times and the settings of the run. the
+// built-in scenario uses the constants of main, and others are read from a
+// file in a small subset of TOML, where every setting may be left out:
+//
+//   [facility]
+//   file = "bikes.txt"             # a Topology, relative to the scenario
+//   capacities = [2, 1, 1, 3, 3]   # or one number for every buffer
+//   routing = "original"
+//
+//   [services]
+//   ws2 = "gamma"                  # by the stream's command line name
+//   insp1 = "triangular(1, 8, 30)"
+//
+//   [run]
+//   generator = "mrg32k3a"
+//   seed = 1                       # replications use its substreams
+//   components = 3000              # the service times of every stream
+//   warmup = "auto"                # or a number of minutes
+//
+//   [stopping]
+//   initial = 10                   # the replications before it applies
+//   max = 200
+//   precision = "occupancy=5%, P1 throughput=0.001@0.99"
+
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub topology: Rc<Topology>,
+    pub generator: Generator,
+    pub seed: u32,
+    pub routing: Routing,
+    // the places in each of Topology::buffers
+    pub capacities: Vec<usize>,
+    // a distribution for each of Topology::services, or None for its own
+    pub services: Vec<Option<String>>,
+    // the service times drawn for each stream, which sets the run length
+    pub components: usize,
+    // the minutes deleted from the start of every replication,
+    // or None to detect them
+    pub warmup: Option<f64>,
+    // the least and the most replications of the sequential stopping
+    // rule, and the interval it requires of each of Measure::all
+    pub replications: (usize, usize),
+    pub targets: Vec<Target>,
+}
+
+impl Scenario {
+    pub fn new(topology: Rc<Topology>) -> Self {
+        Scenario {
+            generator: Generator::Lcg,
+            seed: SEED,
+            routing: Routing::New,
+            // a buffer holds at least what one product takes from it
+            capacities: topology
+                .buffers()
+                .iter()
+                .map(|b| CAPACITY.max(b.needed))
+                .collect(),
+            services: vec![None; topology.services().len()],
+            components: COMPONENT_COUNT,
+            warmup: Some(WARMUP),
+            replications: (INIT_R, MAX_R),
+            targets: default_targets(&topology),
+            topology,
+        }
+    }
+
+    pub fn load(path: &Path) -> std::result::Result<Self, ScenarioError> {
+        let text =
+            fs::read_to_string(path).map_err(|e| ScenarioError::Io(path.to_path_buf(), e))?;
+        let format = |(line, message)| ScenarioError::Format(path.to_path_buf(), line, message);
+        let entries = parse(&text).map_err(format)?;
+
+        // the facility first, as the other settings refer to its elements
+        let topology: Rc<Topology> = match entries
+            .iter()
+            .find(|e| e.section == "facility" && e.key == "file")
+        {
+            None => Rc::default(),
+            Some(entry) => {
+                let file = entry.value.text().map_err(|e| format((entry.line, e)))?;
+                let file = path.parent().unwrap_or(Path::new("")).join(file);
+                Rc::new(Topology::load(&file).map_err(ScenarioError::Facility)?)
+            }
+        };
+        let mut scenario = Scenario::new(topology);
+        for entry in entries.iter() {
+            scenario
+                .set(entry)
+                .map_err(|message| format((entry.line, message)))?;
+        }
+        let (initial, max) = scenario.replications;
+        if initial > max {
+            return Err(ScenarioError::Invalid(
+                path.to_path_buf(),
+                format!(
+                    "the stopping rule starts after {initial} replications, above its max of {max}"
+                ),
+            ));
+        }
+        scenario
+            .check_substreams()
+            .map_err(|message| ScenarioError::Invalid(path.to_path_buf(), message))?;
+        Ok(scenario)
+    }
+
+    // every replication draws from its own substream, so a stopping rule
+    // running more than the generator has would reuse numbers
+    pub fn check_substreams(&self) -> std::result::Result<(), String> {
+        let (_, max) = self.replications;
+        let substreams = self.generator.substreams();
+        match max as u64 <= substreams {
+            true => Ok(()),
+            false => Err(format!(
+                "the stopping rule may run {max} replications, more than \
+                the {substreams} substreams of a {} stream",
+                self.generator
+            )),
+        }
+    }
+
+    fn set(&mut self, entry: &Entry) -> std::result::Result<(), String> {
+        let value = &entry.value;
+        match (entry.section.as_str(), entry.key.as_str()) {
+            // read before any other setting
+            ("facility", "file") => (),
+            ("facility", "capacities") => self.set_capacities(value.wholes()?)?,
+            ("facility", "routing") => self.routing = value.text()?.parse()?,
+            ("services", stream) => self.set_service(stream, value.text()?)?,
+            ("run", "generator") => self.generator = value.text()?.parse()?,
+            ("run", "seed") => match u32::try_from(value.whole()?) {
+                Ok(seed) => self.seed = seed,
+                Err(_) => return Err(format!("a seed must be below {}", u64::from(u32::MAX) + 1)),
+            },
+            ("run", "components") => match value.whole()? {
+                0 => return Err("a run needs at least one component per stream".to_string()),
+                components => self.components = components,
+            },
+            ("run", "warmup") => {
+                self.warmup = match value {
+                    Value::Text(text) if text == "auto" => None,
+                    _ => match value.number() {
+                        Ok(minutes) if minutes >= 0.0 => Some(minutes),
+                        _ => {
+                            return Err("warmup must be a number of minutes or \"auto\"".to_string())
+                        }
+                    },
+                }
+            }
+            ("stopping", "initial") => match value.whole()? {
+                initial if initial >= 2 => self.replications.0 = initial,
+                _ => return Err("an interval needs at least 2 initial replications".to_string()),
+            },
+            ("stopping", "max") => match value.whole()? {
+                max if max >= 2 => self.replications.1 = max,
+                _ => return Err("an interval needs a max of at least 2 replications".to_string()),
+            },
+            ("stopping", "precision") => {
+                parse_targets(&self.topology, value.text()?, &mut self.targets)?
+            }
+            (section, key) => return Err(format!("unknown setting {key:?} in [{section}]")),
+        }
+        Ok(())
+    }
+
+    pub fn set_capacities(&mut self, capacities: Vec<usize>) -> std::result::Result<(), String> {
+        // one capacity for every buffer, or one for each
+        let buffers = self.topology.buffer_names();
+        let capacities = match capacities.len() {
+            1 => vec![capacities[0]; buffers.len()],
+            n if n == buffers.len() => capacities,
+            _ => {
+                return Err(format!(
+                    "capacities must be one whole number, or {} for {}",
+                    buffers.len(),
+                    buffers.join(", ")
+                ))
+            }
+        };
+        // a buffer too small for a product would hold its workstation
+        // up for good, while one of 0 places closes it on purpose
+        let defs = self.topology.buffers();
+        for ((def, name), capacity) in defs.iter().zip(buffers.iter()).zip(capacities.iter()) {
+            if *capacity != 0 && *capacity < def.needed {
+                return Err(format!(
+                    "capacities must give {name} 0 places or at least the {} a product takes",
+                    def.needed
+                ));
+            }
+        }
+        // a workstation with a buffer of 0 places never assembles,
+        // and without any which can there is nothing to measure
+        let closed = |ws: usize| {
+            defs.iter()
+                .zip(capacities.iter())
+                .any(|(b, c)| b.workstation == ws && *c == 0)
+        };
+        if (0..self.topology.workstations.len()).all(closed) {
+            return Err(
+                "capacities leave every workstation with a buffer of 0 places, \
+                so none can assemble a product"
+                    .to_string(),
+            );
+        }
+        self.capacities = capacities;
+        Ok(())
+    }
+
+    fn set_service(&mut self, stream: &str, spec: &str) -> std::result::Result<(), String> {
+        let services = self.topology.services();
+        let i = services
+            .iter()
+            .position(|s| s.stream == stream)
+            .ok_or(format!(
+                "the facility has no service {stream:?}, expected one of {}",
+                services
+                    .iter()
+                    .map(|s| s.stream.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ))?;
+        // a family without parameters is fitted to the observed times
+        // when the model is built, which only the built-in facility has
+        match !spec.contains('(') && self.topology.traced {
+            true => fittable(spec)?,
+            false => distribution::parse(spec, &[]).map(|_| ())?,
+        }
+        self.services[i] = Some(spec.to_string());
+        Ok(())
+    }
+
+    // the settings which can be given on the command line,
+    // by the name of their option without the dashes
+    pub fn options(&self) -> Vec<String> {
+        [
+            "rng",
+            "seed",
+            "routing",
+            "capacities",
+            "warmup",
+            "precision",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .chain(self.topology.services().iter().map(|s| s.stream.clone()))
+        .collect()
+    }
+
+    // sets one of options from its command line value,
+    // i.e ("capacities", "2,1,1,3,3") or ("ws2", "gamma")
+    pub fn set_option(&mut self, name: &str, value: &str) -> std::result::Result<(), String> {
+        match name {
+            "rng" => {
+                self.generator = value.parse()?;
+                self.check_substreams()?
+            }
+            "seed" => {
+                self.seed = value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|e| format!("a seed must be a whole number: {e}"))?
+            }
+            "routing" => self.routing = value.parse()?,
+            "capacities" => {
+                let capacities = value
+                    .split(',')
+                    .map(|capacity| capacity.trim().parse::<usize>())
+                    .collect::<std::result::Result<Vec<usize>, _>>()
+                    .map_err(|e| format!("capacities must be whole numbers: {e}"))?;
+                self.set_capacities(capacities)?
+            }
+            "warmup" => {
+                self.warmup = match value.trim() {
+                    "auto" => None,
+                    minutes => match minutes.parse::<f64>() {
+                        Ok(minutes) if minutes >= 0.0 => Some(minutes),
+                        _ => return Err("warmup must be a number of minutes or auto".to_string()),
+                    },
+                }
+            }
+            "precision" => parse_targets(&self.topology, value, &mut self.targets)?,
+            stream => self.set_service(stream, value)?,
+        }
+        Ok(())
+    }
+}
+
+fn fittable(spec: &str) -> std::result::Result<(), String> {
+    // the families distribution::parse fits to data, and empirical
+    let name = spec.trim().to_lowercase();
+    match name == "empirical" || Family::ALL.iter().any(|f| f.name().to_lowercase() == name) {
+        true => Ok(()),
+        false => Err(format!(
+            "{name:?} cannot be fitted to data, give its parameters"
+        )),
+    }
+}
+
+pub fn default_targets(topology: &Topology) -> Vec<Target> {
+    // occupancies and throughputs to within 2% of their size, and the
+    // ratios, which may be near 0, to within an absolute 0.02
+    Measure::all(topology)
+        .iter()
+        .map(|measure| match measure.is_ratio() {
+            true => Target::new(Precision::Absolute(0.02), 0.95),
+            false => Target::new(Precision::Relative(0.02), 0.95),
+        })
+        .collect()
+}
+
+pub fn parse_targets(
+    topology: &Topology,
+    spec: &str,
+    targets: &mut [Target],
+) -> std::result::Result<(), String> {
+    // a comma separated list of measure=target, i.e
+    // "occupancy=5%,P1 throughput=0.001@0.99". the target applies to every
+    // measure whose name contains the given one, or to all of them for all
+    for entry in spec.split(',') {
+        let (name, target) = entry
+            .split_once('=')
+            .ok_or(format!("expected measure=target, found {entry:?}"))?;
+        let target = target.parse::<Target>()?;
+        let name = name.trim().to_lowercase();
+        let mut matched = false;
+        for (measure, t) in Measure::all(topology).iter().zip(targets.iter_mut()) {
+            if name == "all" || measure.name(topology).to_lowercase().contains(&name) {
+                *t = target;
+                matched = true;
+            }
+        }
+        if !matched {
+            return Err(format!("no measure matches {name:?}"));
+        }
+    }
+    Ok(())
+}
+
+// a value of the TOML subset
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Text(String),
+    Number(f64),
+    List(Vec<f64>),
+}
+
+impl Value {
+    fn text(&self) -> std::result::Result<&str, String> {
+        match self {
+            Self::Text(text) => Ok(text),
+            _ => Err("expected a \"quoted\" string".to_string()),
+        }
+    }
+
+    fn number(&self) -> std::result::Result<f64, String> {
+        match self {
+            Self::Number(x) => Ok(*x),
+            _ => Err("expected a number".to_string()),
+        }
+    }
+
+    fn whole(&self) -> std::result::Result<usize, String> {
+        match self.number()? {
+            x if x >= 0.0 && x.fract() == 0.0 => Ok(x as usize),
+            x => Err(format!("expected a whole number, found {x}")),
+        }
+    }
+
+    fn wholes(&self) -> std::result::Result<Vec<usize>, String> {
+        // one whole number, or a list of them
+        match self {
+            Self::List(list) => list.iter().map(|x| Value::Number(*x).whole()).collect(),
+            _ => Ok(vec![self.whole()?]),
+        }
+    }
+}
+
+// one key = value line, under the [section] before it
+struct Entry {
+    line: usize,
+    section: String,
+    key: String,
+    value: Value,
+}
+
+const SECTIONS: [&str; 4] = ["facility", "services", "run", "stopping"];
+
+fn parse(text: &str) -> std::result::Result<Vec<Entry>, (usize, String)> {
+    let mut entries: Vec<Entry> = vec![];
+    let mut section = None;
+    for (i, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            match SECTIONS.contains(&name) {
+                true => section = Some(name.to_string()),
+                false => {
+                    return Err((
+                        i + 1,
+                        format!(
+                            "unknown section [{name}], expected one of [{}]",
+                            SECTIONS.join("], [")
+                        ),
+                    ))
+                }
+            }
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or((i + 1, format!("expected key = value, found {line:?}")))?;
+        let key = unquote(key.trim()).unwrap_or(key.trim()).to_string();
+        let section = section
+            .clone()
+            .ok_or((i + 1, format!("{key:?} is not in a [section]")))?;
+        if entries.iter().any(|e| e.section == section && e.key == key) {
+            return Err((i + 1, format!("{key:?} is set twice in [{section}]")));
+        }
+        let value = parse_value(value.trim()).map_err(|e| (i + 1, e))?;
+        entries.push(Entry {
+            line: i + 1,
+            section,
+            key,
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+fn strip_comment(line: &str) -> &str {
+    // everything from a # which is not inside a string
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn unquote(s: &str) -> Option<&str> {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .filter(|s| !s.contains('"'))
+}
+
+fn parse_value(s: &str) -> std::result::Result<Value, String> {
+    // a bare word is most likely a string missing its quotes
+    let number = |s: &str| {
+        s.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|x| x.is_finite())
+            .ok_or(format!(
+                "expected a number or a \"quoted\" string, found {}",
+                s.trim()
+            ))
+    };
+    if s.starts_with('"') {
+        return unquote(s)
+            .map(|text| Value::Text(text.to_string()))
+            .ok_or(format!("a string must be one \"quoted\" value, found {s}"));
+    }
+    if let Some(list) = s.strip_prefix('[') {
+        let list = list
+            .strip_suffix(']')
+            .ok_or(format!("missing ']' in {s}"))?;
+        return list
+            .split(',')
+            .map(number)
+            .collect::<std::result::Result<Vec<f64>, String>>()
+            .map(Value::List);
+    }
+    number(s).map(Value::Number)
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(PathBuf, io::Error),
+    // the line, and what was wrong with it
+    Format(PathBuf, usize, String),
+    // a problem with the scenario as a whole
+    Invalid(PathBuf, String),
+    Facility(FacilityError),
+}
+
+impl Display for ScenarioError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            Self::Format(path, line, message) => {
+                write!(f, "{}:{}: {}", path.display(), line, message)
+            }
+            Self::Invalid(path, message) => write!(f, "{}: {}", path.display(), message),
+            Self::Facility(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes a scenario, and a facility it may refer to as bikes.txt,
+    // to a directory of the test's own, then loads it
+    fn load(test: &str, text: &str) -> std::result::Result<Scenario, String> {
+        let dir = std::env::temp_dir().join(format!("scenario-{}-{test}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let facility = "\
+            component Frame exponential(4)\n\
+            component Wheel exponential(3)\n\
+            product Bike Frame Wheel Wheel\n\
+            workstation Line1 Bike exponential(8)\n\
+            workstation Line2 Bike exponential(9)\n\
+            inspector Alice Frame Wheel\n";
+        fs::write(dir.join("bikes.txt"), facility).unwrap();
+        fs::write(dir.join("scenario.toml"), text).unwrap();
+        let scenario = Scenario::load(&dir.join("scenario.toml"));
+        fs::remove_dir_all(&dir).unwrap();
+        // the error without the directory, i.e "scenario.toml:2: ..."
+        scenario.map_err(|e| e.to_string().replace(&format!("{}/", dir.display()), ""))
+    }
+
+    #[test]
+    fn loads_every_setting() {
+        let scenario = load(
+            "settings",
+            "[facility]\n\
+            file = \"bikes.txt\"\n\
+            capacities = [3, 2, 0, 4]   # Line2 is closed\n\
+            routing = \"original\"\n\
+            [services]\n\
+            line1 = \"gamma(2, 4)\"\n\
+            [run]\n\
+            generator = \"pcg64\"\n\
+            seed = 7\n\
+            warmup = \"auto\"\n\
+            [stopping]\n\
+            initial = 5\n\
+            max = 50\n",
+        )
+        .unwrap();
+        assert_eq!(scenario.topology.workstations.len(), 2);
+        assert_eq!(scenario.capacities, vec![3, 2, 0, 4]);
+        assert_eq!(scenario.routing, Routing::Original);
+        assert_eq!(scenario.services[0].as_deref(), Some("gamma(2, 4)"));
+        assert_eq!(scenario.generator, Generator::Pcg64);
+        assert_eq!(scenario.seed, 7);
+        assert_eq!(scenario.warmup, None);
+        assert_eq!(scenario.replications, (5, 50));
+    }
+
+    #[test]
+    fn format_errors_give_the_line() {
+        for (test, text, error) in [
+            (
+                "section",
+                "[run]\nseed = 1\n[runs]\n",
+                "scenario.toml:3: unknown section [runs], \
+                expected one of [facility], [services], [run], [stopping]",
+            ),
+            (
+                "outside",
+                "seed = 1\n",
+                "scenario.toml:1: \"seed\" is not in a [section]",
+            ),
+            (
+                "twice",
+                "[run]\nseed = 1\nseed = 2\n",
+                "scenario.toml:3: \"seed\" is set twice in [run]",
+            ),
+            (
+                "unknown",
+                "[run]\nspeed = 1\n",
+                "scenario.toml:2: unknown setting \"speed\" in [run]",
+            ),
+            (
+                "unquoted",
+                "[facility]\nrouting = original\n",
+                "scenario.toml:2: expected a number or a \"quoted\" string, found original",
+            ),
+            (
+                "warmup",
+                "[run]\nwarmup = -5\n",
+                "scenario.toml:2: warmup must be a number of minutes or \"auto\"",
+            ),
+            (
+                "stream",
+                "[services]\nws4 = \"gamma\"\n",
+                "scenario.toml:2: the facility has no service \"ws4\", \
+                expected one of ws1, ws2, ws3, insp1, insp22, insp23",
+            ),
+        ] {
+            assert_eq!(load(test, text).unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn rejects_capacities_which_stop_assembly() {
+        let capacities = |test: &str, list: &str| {
+            load(
+                test,
+                &format!("[facility]\nfile = \"bikes.txt\"\ncapacities = {list}\n"),
+            )
+        };
+        assert_eq!(
+            capacities("few", "[2, 1, 2, 2]").unwrap_err(),
+            "scenario.toml:3: capacities must give Wheel of Line1 \
+            0 places or at least the 2 a product takes"
+        );
+        assert_eq!(
+            capacities("closed", "[0, 2, 2, 0]").unwrap_err(),
+            "scenario.toml:3: capacities leave every workstation with a buffer \
+            of 0 places, so none can assemble a product"
+        );
+        assert_eq!(
+            capacities("count", "[2, 2]").unwrap_err(),
+            "scenario.toml:3: capacities must be one whole number, or 4 for \
+            Frame of Line1, Wheel of Line1, Frame of Line2, Wheel of Line2"
+        );
+        assert_eq!(capacities("one", "2").unwrap().capacities, vec![2; 4]);
+    }
+
+    #[test]
+    fn rejects_a_stopping_rule_starting_after_its_max() {
+        assert_eq!(
+            load("stopping", "[stopping]\ninitial = 20\nmax = 10\n").unwrap_err(),
+            "scenario.toml: the stopping rule starts after 20 replications, above its max of 10"
+        );
+    }
+
+    #[test]
+    fn rejects_more_replications_than_substreams() {
+        assert_eq!(
+            load("substreams", "[stopping]\ninitial = 1030\nmax = 1030\n").unwrap_err(),
+            "scenario.toml: the stopping rule may run 1030 replications, \
+            more than the 1024 substreams of a lcg stream"
+        );
+        let mut scenario = load(
+            "mrg",
+            "[run]\ngenerator = \"mrg32k3a\"\n[stopping]\nmax = 1030\n",
+        )
+        .unwrap();
+        assert!(scenario.set_option("rng", "lcg").is_err());
+    }
+
+    #[test]
+    fn options_override_the_scenario() {
+        let mut scenario = Scenario::new(Rc::default());
+        scenario.set_option("capacities", "1, 2, 3, 1, 1").unwrap();
+        assert_eq!(scenario.capacities, vec![1, 2, 3, 1, 1]);
+        scenario.set_option("warmup", "auto").unwrap();
+        assert_eq!(scenario.warmup, None);
+        assert!(scenario.set_option("seed", "-1").is_err());
+        assert!(scenario.set_option("ws2", "gamma").is_ok());
+        assert!(scenario.set_option("ws2", "wobbly").is_err());
+    }
+}
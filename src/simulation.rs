@@ -1,8 +1,14 @@
+use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
 use std::fmt::{Display, Formatter, Result};
+use std::num::ParseFloatError;
 use std::ops::{Add, AddAssign, Mul, Sub};
+use std::rc::Rc;
+use std::str::FromStr;
 
-use crate::event::FacilityEvent;
+use crate::checkpoint::{Checkpoint, CheckpointError, Reader, Writer};
+use crate::event::{FacilityEvent, Topic};
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
 pub struct TimeStamp {
@@ -62,6 +68,7 @@ impl Display for Duration {
     }
 }
 
+#[allow(clippy::derive_ord_xor_partial_ord)]
 impl Ord for Duration {
     fn cmp(&self, other: &Self) -> Ordering {
         match f64::min(self.minutes, other.minutes) == self.minutes {
@@ -73,13 +80,18 @@ impl Ord for Duration {
 
 impl Eq for Duration {}
 
-impl Duration {
-    pub fn from(s: String) -> Self {
-        Duration {
-            minutes: s.parse::<f64>().unwrap(),
-        }
+impl FromStr for Duration {
+    type Err = ParseFloatError;
+
+    // parses a number of minutes, i.e "10.160"
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Duration {
+            minutes: s.trim().parse::<f64>()?,
+        })
     }
+}
 
+impl Duration {
     pub fn of_minutes(m: f64) -> Self {
         Duration { minutes: m }
     }
@@ -137,8 +149,391 @@ impl Mul<Duration> for f64 {
 
 // a simulation actor can either respond to some
 // event or just produce an event in response to time passing.
-pub trait SimulationActor {
-    fn respond_to(&mut self, _: FacilityEvent) -> Option<FacilityEvent>;
-    fn respond(&mut self, now: TimeStamp) -> Option<FacilityEvent>;
-    fn duration_until_next_event(&self, now: TimeStamp) -> Option<Duration>;
+// it is only told of the events of its topics, and keeps its
+// own entry in the future event list up to date through schedule
+pub trait SimulationActor: Display {
+    fn topics(&self) -> Vec<Topic>;
+    fn respond_to(&mut self, _: FacilityEvent, schedule: &mut Schedule) -> Option<FacilityEvent>;
+    fn respond(&mut self, now: TimeStamp, schedule: &mut Schedule) -> Option<FacilityEvent>;
+}
+
+// an actor's handle on its entry in the future event list
+pub struct Schedule<'a> {
+    events: &'a mut FutureEventList,
+    actor: usize,
+}
+
+impl Schedule<'_> {
+    pub fn set(&mut self, next: Option<TimeStamp>) {
+        // the time respond should next be called, if any, leaving
+        // the entry alone if the actor's next event hasn't moved
+        if next != self.events.scheduled_time(self.actor) {
+            match next {
+                Some(ts) => self.events.schedule(self.actor, ts),
+                None => self.events.cancel(self.actor),
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct ScheduledEvent {
+    time: TimeStamp,
+    sequence: u64,
+    actor: usize,
+}
+
+impl PartialEq for ScheduledEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScheduledEvent {}
+
+impl PartialOrd for ScheduledEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScheduledEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed so the BinaryHeap (a max heap) pops the earliest event,
+        // with ties going to whichever was scheduled first
+        other
+            .time
+            .minutes
+            .total_cmp(&self.time.minutes)
+            .then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+// the future event list. each actor has at most one pending
+// event. rescheduling or cancelling leaves the old entry in the
+// heap, where it is skipped once it surfaces, so every operation
+// is O(log n) in the number of pending events.
+pub struct FutureEventList {
+    heap: BinaryHeap<ScheduledEvent>,
+    // the live (sequence, time) of each actor's pending event
+    pending: Vec<Option<(u64, TimeStamp)>>,
+    next_sequence: u64,
+}
+
+impl FutureEventList {
+    pub fn new(actor_count: usize) -> Self {
+        FutureEventList {
+            heap: BinaryHeap::new(),
+            pending: vec![None; actor_count],
+            next_sequence: 0,
+        }
+    }
+
+    pub fn scheduled_time(&self, actor: usize) -> Option<TimeStamp> {
+        self.pending[actor].map(|(_, time)| time)
+    }
+
+    pub fn schedule(&mut self, actor: usize, time: TimeStamp) {
+        // replaces any event already pending for the actor
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.pending[actor] = Some((sequence, time));
+        self.heap.push(ScheduledEvent {
+            time,
+            sequence,
+            actor,
+        });
+    }
+
+    pub fn cancel(&mut self, actor: usize) {
+        self.pending[actor] = None;
+    }
+
+    pub fn peek(&mut self) -> Option<TimeStamp> {
+        // the time of the next live event, dropping stale entries on the way
+        while let Some(event) = self.heap.peek().copied() {
+            if self.pending[event.actor].map(|(sequence, _)| sequence) == Some(event.sequence) {
+                return Some(event.time);
+            }
+            self.heap.pop();
+        }
+        None
+    }
+
+    pub fn pop(&mut self) -> Option<(usize, TimeStamp)> {
+        while let Some(event) = self.heap.pop() {
+            if self.pending[event.actor].map(|(sequence, _)| sequence) == Some(event.sequence) {
+                self.pending[event.actor] = None;
+                return Some((event.actor, event.time));
+            }
+        }
+        None
+    }
+}
+
+// runs a facility made of any mix of actors. every event an
+// actor produces goes to the actors listening for its topic
+pub struct FacilitySimulation {
+    actors: Vec<Rc<RefCell<dyn SimulationActor>>>,
+    // the actors of each topic, in the order they were given
+    listeners: HashMap<Topic, Vec<usize>>,
+    clock: TimeStamp,
+    events: FutureEventList,
+    // what happened when, kept only while tracing: every event
+    // broadcast and the state of each actor after it responded
+    event_log: Option<Vec<(TimeStamp, String)>>,
+}
+
+impl FacilitySimulation {
+    pub fn new(actors: Vec<Rc<RefCell<dyn SimulationActor>>>) -> Self {
+        let mut listeners = HashMap::<Topic, Vec<usize>>::new();
+        for (index, actor) in actors.iter().enumerate() {
+            for topic in actor.borrow().topics() {
+                listeners.entry(topic).or_default().push(index);
+            }
+        }
+        FacilitySimulation {
+            events: FutureEventList::new(actors.len()),
+            actors,
+            listeners,
+            clock: TimeStamp::start(),
+            event_log: None,
+        }
+    }
+
+    pub fn keep_log(&mut self) {
+        self.event_log = Some(vec![]);
+    }
+
+    pub fn event_log(&self) -> &[(TimeStamp, String)] {
+        self.event_log.as_deref().unwrap_or(&[])
+    }
+
+    pub fn run(&mut self) -> Duration {
+        // runs a simulation to completion and
+        // returns the total simulated time
+        self.start();
+        self.resume()
+    }
+
+    pub fn start(&mut self) {
+        self.dispatch_to_simulation_actors(FacilityEvent::SimulationStarted);
+    }
+
+    pub fn run_until(&mut self, time: TimeStamp) {
+        // processes every event before time, pausing
+        // the simulation just before the first one after it
+        while self.events.peek().is_some_and(|ts| ts < time) {
+            self.step();
+        }
+    }
+
+    pub fn resume(&mut self) -> Duration {
+        // runs a started or restored simulation to completion
+        while self.step() {}
+        self.clock - TimeStamp::start()
+    }
+
+    pub fn clock(&self) -> TimeStamp {
+        self.clock
+    }
+
+    fn step(&mut self) -> bool {
+        // processes the next event, if there is one
+        let Some((next_actor_index, ts)) = self.events.pop() else {
+            return false;
+        };
+        self.clock = ts;
+        log!("Time: {}", self.clock);
+        log!(
+            "actor: {}\t{}",
+            next_actor_index,
+            self.actors[next_actor_index].borrow()
+        );
+        let mut schedule = Schedule {
+            events: &mut self.events,
+            actor: next_actor_index,
+        };
+        let responses = self.actors[next_actor_index]
+            .borrow_mut()
+            .respond(self.clock, &mut schedule);
+        if let Some(log) = self.event_log.as_mut() {
+            let actor = self.actors[next_actor_index].borrow().to_string();
+            log.push((self.clock, actor));
+        }
+        for response in responses.into_iter() {
+            self.dispatch_to_simulation_actors(response);
+        }
+        true
+    }
+
+    fn dispatch_to_simulation_actors(&mut self, event: FacilityEvent) {
+        // an actor may answer with an event of its own, i.e an
+        // unblocked inspector starting an idle workstation
+        if let Some(log) = self.event_log.as_mut() {
+            log.push((self.clock, event.to_string()));
+        }
+        let listeners = self
+            .listeners
+            .get(&event.topic())
+            .map_or(&[][..], |l| l.as_slice());
+        let mut responses = vec![];
+        for &index in listeners {
+            let mut schedule = Schedule {
+                events: &mut self.events,
+                actor: index,
+            };
+            let response = self.actors[index]
+                .borrow_mut()
+                .respond_to(event.clone(), &mut schedule);
+            responses.extend(response);
+        }
+        for response in responses {
+            self.dispatch_to_simulation_actors(response);
+        }
+    }
+}
+
+impl Checkpoint for FacilitySimulation {
+    // the clock and the live events of the future event list. the
+    // actors are saved by whoever built the facility, which knows
+    // what they are
+    fn save(&self, out: &mut Writer) {
+        out.line("clock").time(Some(self.clock));
+        let live = self
+            .events
+            .pending
+            .iter()
+            .enumerate()
+            .filter_map(|(actor, event)| event.map(|(sequence, time)| (actor, sequence, time)))
+            .collect::<Vec<(usize, u64, TimeStamp)>>();
+        out.line("events")
+            .value(self.events.next_sequence)
+            .value(live.len());
+        for (actor, sequence, time) in live {
+            out.line("event")
+                .value(actor)
+                .value(sequence)
+                .time(Some(time));
+        }
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> std::result::Result<(), CheckpointError> {
+        input.expect("clock")?;
+        self.clock = input
+            .time()?
+            .ok_or_else(|| input.error("the clock cannot be missing".to_string()))?;
+        input.expect("events")?;
+        let mut events = FutureEventList::new(self.actors.len());
+        events.next_sequence = input.value()?;
+        for _ in 0..input.value::<usize>()? {
+            input.expect("event")?;
+            let actor = input.value::<usize>()?;
+            let sequence = input.value::<u64>()?;
+            let time = input
+                .time()?
+                .ok_or_else(|| input.error("an event needs a time".to_string()))?;
+            if actor >= self.actors.len() || sequence >= events.next_sequence {
+                return Err(input.error(format!("event {sequence} for actor {actor} is invalid")));
+            }
+            events.pending[actor] = Some((sequence, time));
+            events.heap.push(ScheduledEvent {
+                time,
+                sequence,
+                actor,
+            });
+        }
+        self.events = events;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: f64) -> TimeStamp {
+        TimeStamp { minutes }
+    }
+
+    #[test]
+    fn ties_come_out_in_the_order_scheduled() {
+        let mut events = FutureEventList::new(4);
+        events.schedule(2, at(5.0));
+        events.schedule(0, at(5.0));
+        events.schedule(3, at(1.0));
+        events.schedule(1, at(5.0));
+        let order = std::iter::from_fn(|| events.pop()).collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            vec![(3, at(1.0)), (2, at(5.0)), (0, at(5.0)), (1, at(5.0))]
+        );
+    }
+
+    #[test]
+    fn rescheduling_drops_the_earlier_entry() {
+        let mut events = FutureEventList::new(2);
+        events.schedule(0, at(1.0));
+        events.schedule(1, at(2.0));
+        events.schedule(0, at(3.0));
+        assert_eq!(events.scheduled_time(0), Some(at(3.0)));
+        assert_eq!(events.peek(), Some(at(2.0)));
+        assert_eq!(events.pop(), Some((1, at(2.0))));
+        assert_eq!(events.pop(), Some((0, at(3.0))));
+        assert_eq!(events.pop(), None);
+
+        events.schedule(1, at(4.0));
+        events.cancel(1);
+        assert_eq!(events.peek(), None);
+    }
+
+    // an actor with an event every interval minutes
+    struct Ticker {
+        interval: f64,
+        ticks: usize,
+    }
+
+    impl Display for Ticker {
+        fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+            write!(f, "ticked {} times", self.ticks)
+        }
+    }
+
+    impl SimulationActor for Ticker {
+        fn topics(&self) -> Vec<Topic> {
+            vec![Topic::SimulationStarted]
+        }
+
+        fn respond_to(
+            &mut self,
+            _: FacilityEvent,
+            schedule: &mut Schedule,
+        ) -> Option<FacilityEvent> {
+            schedule.set(Some(at(self.interval)));
+            None
+        }
+
+        fn respond(&mut self, now: TimeStamp, schedule: &mut Schedule) -> Option<FacilityEvent> {
+            self.ticks += 1;
+            schedule.set(Some(now + Duration::of_minutes(self.interval)));
+            None
+        }
+    }
+
+    #[test]
+    fn run_until_stops_before_the_time() {
+        let ticker = Rc::new(RefCell::new(Ticker {
+            interval: 10.0,
+            ticks: 0,
+        }));
+        let mut simulation = FacilitySimulation::new(vec![ticker.clone()]);
+        simulation.start();
+        simulation.run_until(at(25.0));
+        assert_eq!((simulation.clock(), ticker.borrow().ticks), (at(20.0), 2));
+        // an event at exactly the time is left for later
+        simulation.run_until(at(30.0));
+        assert_eq!((simulation.clock(), ticker.borrow().ticks), (at(20.0), 2));
+        simulation.run_until(at(30.5));
+        assert_eq!((simulation.clock(), ticker.borrow().ticks), (at(30.0), 3));
+    }
 }
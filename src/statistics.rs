@@ -0,0 +1,236 @@
+use std::fmt::{Display, Formatter, Result};
+use std::str::FromStr;
+
+use crate::special::student_t_quantile;
+
+// summary statistics of independent observations of a measure,
+// such as its value in each replication or each batch mean, and
+// Student-t confidence intervals for its expectation.
+
+pub fn mean(x: &[f64]) -> f64 {
+    x.iter().sum::<f64>() / x.len() as f64
+}
+
+pub fn variance(x: &[f64]) -> f64 {
+    // the unbiased sample variance, which needs two observations
+    let mean = mean(x);
+    x.iter().map(|xi| (xi - mean).powi(2)).sum::<f64>() / (x.len() as f64 - 1.0)
+}
+
+pub fn t_critical(confidence: f64, df: f64) -> f64 {
+    // the two sided critical value, i.e 2.262 for 95% with 9 df
+    student_t_quantile(0.5 + confidence / 2.0, df)
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ConfidenceInterval {
+    pub mean: f64,
+    pub half_width: f64,
+    pub confidence: f64,
+}
+
+impl ConfidenceInterval {
+    pub fn new(mean: f64, std_error: f64, df: f64, confidence: f64) -> Self {
+        ConfidenceInterval {
+            mean,
+            half_width: t_critical(confidence, df) * std_error,
+            confidence,
+        }
+    }
+
+    pub fn from_sample(x: &[f64], confidence: f64) -> Option<Self> {
+        // None without the two observations a variance needs
+        let n = x.len();
+        match n < 2 {
+            true => None,
+            false => Some(Self::new(
+                mean(x),
+                (variance(x) / n as f64).sqrt(),
+                (n - 1) as f64,
+                confidence,
+            )),
+        }
+    }
+
+    pub fn low(&self) -> f64 {
+        self.mean - self.half_width
+    }
+
+    pub fn high(&self) -> f64 {
+        self.mean + self.half_width
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.low() <= value && value <= self.high()
+    }
+}
+
+impl Display for ConfidenceInterval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        // the precision, if given, applies to both numbers
+        match f.precision() {
+            Some(p) => write!(f, "{:.*} +- {:.*}", p, self.mean, p, self.half_width),
+            None => write!(f, "{} +- {}", self.mean, self.half_width),
+        }
+    }
+}
+
+pub fn paired_difference(a: &[f64], b: &[f64], confidence: f64) -> Option<ConfidenceInterval> {
+    // the interval for the mean of the differences a - b. pairing removes
+    // the variation the two share, i.e through common random numbers, so
+    // it is much narrower than comparing a and b separately
+    let d = a
+        .iter()
+        .zip(b.iter())
+        .map(|(a, b)| a - b)
+        .collect::<Vec<f64>>();
+    ConfidenceInterval::from_sample(&d, confidence)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Precision {
+    // the half width may be at most this
+    Absolute(f64),
+    // the half width may be at most this fraction of the mean
+    Relative(f64),
+}
+
+impl Precision {
+    pub fn met(&self, interval: &ConfidenceInterval) -> bool {
+        match self {
+            Self::Absolute(bound) => interval.half_width <= *bound,
+            Self::Relative(fraction) => interval.half_width <= fraction * interval.mean.abs(),
+        }
+    }
+
+    // the half width in the terms of the target, to compare with it
+    pub fn achieved(&self, interval: &ConfidenceInterval) -> f64 {
+        match self {
+            Self::Absolute(_) => interval.half_width,
+            Self::Relative(_) => interval.half_width / interval.mean.abs(),
+        }
+    }
+}
+
+impl Display for Precision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Absolute(bound) => write!(f, "+-{bound}"),
+            Self::Relative(fraction) => write!(f, "{}%", fraction * 100.0),
+        }
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // i.e 0.02 for an absolute half width or 5% for a relative one
+        let s = s.trim();
+        let (number, relative) = match s.strip_suffix('%') {
+            Some(number) => (number, true),
+            None => (s, false),
+        };
+        match (number.trim().parse::<f64>(), relative) {
+            (Ok(x), true) if x > 0.0 => Ok(Self::Relative(x / 100.0)),
+            (Ok(x), false) if x > 0.0 => Ok(Self::Absolute(x)),
+            _ => Err(format!(
+                "invalid precision {s:?}, expected a positive half width or percentage"
+            )),
+        }
+    }
+}
+
+// what a sequential stopping rule requires of the interval of one measure
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Target {
+    pub precision: Precision,
+    pub confidence: f64,
+}
+
+impl Target {
+    pub fn new(precision: Precision, confidence: f64) -> Self {
+        Target {
+            precision,
+            confidence,
+        }
+    }
+
+    pub fn met(&self, interval: &ConfidenceInterval) -> bool {
+        self.precision.met(interval)
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} at {}%", self.precision, self.confidence * 100.0)
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // a precision, optionally followed by @ and a confidence level,
+        // i.e 5%@0.99. the level is 95% unless given
+        let (precision, confidence) = match s.split_once('@') {
+            Some((precision, confidence)) => match confidence.trim().parse::<f64>() {
+                Ok(c) if c > 0.0 && c < 1.0 => (precision, c),
+                _ => {
+                    return Err(format!(
+                        "invalid confidence level {confidence:?}, expected a number between 0 and 1"
+                    ))
+                }
+            },
+            None => (s, 0.95),
+        };
+        Ok(Target::new(precision.parse()?, confidence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_of_a_sample() {
+        // mean 5.5 and variance 55 / 6 over 10 observations, 9 df
+        let x = (1..=10).map(|i| i as f64).collect::<Vec<f64>>();
+        let interval = ConfidenceInterval::from_sample(&x, 0.95).unwrap();
+        assert_eq!(interval.mean, 5.5);
+        let half_width = 2.262157 * (55.0 / 6.0 / 10.0_f64).sqrt();
+        assert!((interval.half_width - half_width).abs() < 1e-5);
+        assert!(interval.contains(5.5) && !interval.contains(8.0));
+        assert_eq!(format!("{interval:.2}"), "5.50 +- 2.17");
+    }
+
+    #[test]
+    fn interval_needs_two_observations() {
+        assert!(ConfidenceInterval::from_sample(&[], 0.95).is_none());
+        assert!(ConfidenceInterval::from_sample(&[1.0], 0.95).is_none());
+    }
+
+    #[test]
+    fn paired_difference_removes_what_is_shared() {
+        // b is a plus a constant, so the differences do not vary at all
+        let a = [3.0, 8.0, 1.0, 6.0];
+        let b = a.map(|x| x + 2.0);
+        let interval = paired_difference(&a, &b, 0.95).unwrap();
+        assert_eq!(interval.mean, -2.0);
+        assert_eq!(interval.half_width, 0.0);
+    }
+
+    #[test]
+    fn targets_parse() {
+        assert_eq!(
+            "5%@0.99".parse::<Target>(),
+            Ok(Target::new(Precision::Relative(0.05), 0.99))
+        );
+        assert_eq!(
+            "0.02".parse::<Target>(),
+            Ok(Target::new(Precision::Absolute(0.02), 0.95))
+        );
+        assert!("-1".parse::<Target>().is_err());
+        assert!("5%@1.5".parse::<Target>().is_err());
+    }
+}
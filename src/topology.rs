@@ -0,0 +1,521 @@
+use std::fmt::{Display, Formatter, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::distribution;
+
+// the structure of a facility: the components its inspectors inspect,
+// the products assembled from them and the workstations assembling each
+// product. the built-in facility is the one the .dat files were observed
+// on, and others are read from a file with one definition per line:
+//
+//   component NAME INSPECTION-TIMES
+//   product NAME COMPONENT...
+//   workstation NAME PRODUCT ASSEMBLY-TIMES
+//   inspector NAME COMPONENT...
+//
+// where the times are distributions such as "triangular(1, 8, 30)". a
+// product may list a component more than once to take that many of it,
+// and lines starting with # are comments
+
+// an element of a facility, by its place in the list of its kind,
+// with its own copy of the name to show it by
+#[derive(Clone, Debug)]
+pub struct Kind {
+    pub index: usize,
+    pub name: Rc<str>,
+}
+
+impl PartialEq for Kind {
+    fn eq(&self, other: &Kind) -> bool {
+        self.index == other.index
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.pad(&self.name)
+    }
+}
+
+// where the service times of one stream come from
+#[derive(Clone, Debug)]
+pub struct Service {
+    // the name it is given a distribution by on the command line
+    pub stream: String,
+    // a distribution, or a family which is fitted to the observed
+    // times of the stream, which only the built-in facility has
+    pub spec: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ComponentDef {
+    pub name: String,
+    pub inspection: Service,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProductDef {
+    pub name: String,
+    // the index of every component it takes, i.e [0, 0, 1] for two C1 and a C2
+    pub recipe: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct WorkstationDef {
+    pub name: String,
+    pub product: usize,
+    pub assembly: Service,
+}
+
+#[derive(Clone, Debug)]
+pub struct InspectorDef {
+    pub name: String,
+    pub components: Vec<usize>,
+}
+
+// the queue of one kind of component in front of a workstation
+#[derive(Copy, Clone, Debug)]
+pub struct BufferDef {
+    pub workstation: usize,
+    pub component: usize,
+    // the number of the component each product takes
+    pub needed: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Topology {
+    pub components: Vec<ComponentDef>,
+    pub products: Vec<ProductDef>,
+    pub workstations: Vec<WorkstationDef>,
+    pub inspectors: Vec<InspectorDef>,
+    // whether the service times were observed, in the .dat files of
+    // trace::DATA_DIR in the order of services, so they can be replayed
+    pub traced: bool,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        // WS1 assembles P1 from a C1, WS2 P2 from a C1 and a C2 and WS3
+        // P3 from a C1 and a C3. Inspector1 inspects every C1 and
+        // Inspector2 both C2 and C3, with the exponential distributions
+        // fitted to the observed times of each
+        let service = |stream: &str| Service {
+            stream: stream.to_string(),
+            spec: "exponential".to_string(),
+        };
+        let component = |name: &str, stream: &str| ComponentDef {
+            name: name.to_string(),
+            inspection: service(stream),
+        };
+        let product = |name: &str, recipe: Vec<usize>| ProductDef {
+            name: name.to_string(),
+            recipe,
+        };
+        let workstation = |name: &str, product: usize, stream: &str| WorkstationDef {
+            name: name.to_string(),
+            product,
+            assembly: service(stream),
+        };
+        let inspector = |name: &str, components: Vec<usize>| InspectorDef {
+            name: name.to_string(),
+            components,
+        };
+        Topology {
+            components: vec![
+                component("C1", "insp1"),
+                component("C2", "insp22"),
+                component("C3", "insp23"),
+            ],
+            products: vec![
+                product("P1", vec![0]),
+                product("P2", vec![0, 1]),
+                product("P3", vec![0, 2]),
+            ],
+            workstations: vec![
+                workstation("WS1", 0, "ws1"),
+                workstation("WS2", 1, "ws2"),
+                workstation("WS3", 2, "ws3"),
+            ],
+            inspectors: vec![
+                inspector("Inspector1", vec![0]),
+                inspector("Inspector2", vec![1, 2]),
+            ],
+            traced: true,
+        }
+    }
+}
+
+impl Topology {
+    pub fn component(&self, index: usize) -> Kind {
+        Kind {
+            index,
+            name: Rc::from(self.components[index].name.as_str()),
+        }
+    }
+
+    pub fn product(&self, index: usize) -> Kind {
+        Kind {
+            index,
+            name: Rc::from(self.products[index].name.as_str()),
+        }
+    }
+
+    pub fn workstation(&self, index: usize) -> Kind {
+        Kind {
+            index,
+            name: Rc::from(self.workstations[index].name.as_str()),
+        }
+    }
+
+    pub fn buffers(&self) -> Vec<BufferDef> {
+        // workstation by workstation, each with a buffer for every
+        // component of its product in the order the recipe lists them
+        let mut buffers: Vec<BufferDef> = vec![];
+        for (workstation, ws) in self.workstations.iter().enumerate() {
+            for &component in self.products[ws.product].recipe.iter() {
+                match buffers
+                    .iter_mut()
+                    .find(|b| b.workstation == workstation && b.component == component)
+                {
+                    Some(buffer) => buffer.needed += 1,
+                    None => buffers.push(BufferDef {
+                        workstation,
+                        component,
+                        needed: 1,
+                    }),
+                }
+            }
+        }
+        buffers
+    }
+
+    // i.e "C1 of WS2"
+    pub fn buffer_names(&self) -> Vec<String> {
+        self.buffers()
+            .iter()
+            .map(|b| {
+                format!(
+                    "{} of {}",
+                    self.components[b.component].name, self.workstations[b.workstation].name
+                )
+            })
+            .collect()
+    }
+
+    // the assembly of each workstation then the inspection of each
+    // component, the order their random streams are created in
+    pub fn services(&self) -> Vec<&Service> {
+        self.workstations
+            .iter()
+            .map(|ws| &ws.assembly)
+            .chain(self.components.iter().map(|c| &c.inspection))
+            .collect()
+    }
+
+    pub fn uses(&self, workstation: usize, component: usize) -> bool {
+        self.products[self.workstations[workstation].product]
+            .recipe
+            .contains(&component)
+    }
+
+    pub fn load(path: &Path) -> std::result::Result<Self, FacilityError> {
+        let text =
+            fs::read_to_string(path).map_err(|e| FacilityError::Io(path.to_path_buf(), e))?;
+        Self::parse(&text).map_err(|(line, message)| match line {
+            0 => FacilityError::Invalid(path.to_path_buf(), message),
+            line => FacilityError::Format(path.to_path_buf(), line, message),
+        })
+    }
+
+    fn parse(text: &str) -> std::result::Result<Self, (usize, String)> {
+        // the line, label, name and remaining words of every definition,
+        // which are resolved once all have been read so any order works
+        let mut definitions = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let label = match words.next() {
+                Some(label) if !label.starts_with('#') => label,
+                _ => continue,
+            };
+            if !["component", "product", "workstation", "inspector"].contains(&label) {
+                return Err((
+                    i + 1,
+                    format!(
+                        "unknown definition {label:?}, expected component, \
+                        product, workstation or inspector"
+                    ),
+                ));
+            }
+            let name = words
+                .next()
+                .ok_or((i + 1, format!("a {label} needs a name")))?;
+            if definitions
+                .iter()
+                .any(|(_, l, n, _)| *l == label && *n == name)
+            {
+                return Err((i + 1, format!("{label} {name} is defined twice")));
+            }
+            definitions.push((i + 1, label, name, words.collect::<Vec<&str>>()));
+        }
+        let of = |label: &str| {
+            definitions
+                .iter()
+                .filter(|(_, l, _, _)| *l == label)
+                .map(|(line, _, name, words)| (*line, *name, words.as_slice()))
+                .collect::<Vec<(usize, &str, &[&str])>>()
+        };
+        let (components, products, workstations, inspectors) = (
+            of("component"),
+            of("product"),
+            of("workstation"),
+            of("inspector"),
+        );
+        for (label, list) in [
+            ("component", &components),
+            ("product", &products),
+            ("workstation", &workstations),
+            ("inspector", &inspectors),
+        ] {
+            if list.is_empty() {
+                return Err((0, format!("the facility has no {label}")));
+            }
+        }
+
+        let find = |list: &[(usize, &str, &[&str])], label: &str, name: &str, line: usize| {
+            list.iter()
+                .position(|(_, n, _)| *n == name)
+                .ok_or((line, format!("no {label} {name:?}")))
+        };
+        let service = |name: &str, spec: &[&str], line: usize| {
+            // there are no observed times to fit a family to
+            let spec = spec.join(" ");
+            match distribution::parse(&spec, &[]) {
+                Ok(_) => Ok(Service {
+                    stream: name.to_lowercase(),
+                    spec,
+                }),
+                Err(e) => Err((line, format!("the service times of {name}: {e}"))),
+            }
+        };
+        let topology = Topology {
+            components: components
+                .iter()
+                .map(|(line, name, words)| {
+                    Ok(ComponentDef {
+                        name: name.to_string(),
+                        inspection: service(name, words, *line)?,
+                    })
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            products: products
+                .iter()
+                .map(|(line, name, words)| match words.is_empty() {
+                    true => Err((*line, format!("product {name} needs components"))),
+                    false => Ok(ProductDef {
+                        name: name.to_string(),
+                        recipe: words
+                            .iter()
+                            .map(|c| find(&components, "component", c, *line))
+                            .collect::<std::result::Result<_, _>>()?,
+                    }),
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            workstations: workstations
+                .iter()
+                .map(|(line, name, words)| {
+                    let product = words
+                        .first()
+                        .ok_or((*line, format!("workstation {name} needs a product")))?;
+                    Ok(WorkstationDef {
+                        name: name.to_string(),
+                        product: find(&products, "product", product, *line)?,
+                        assembly: service(name, &words[1..], *line)?,
+                    })
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            inspectors: inspectors
+                .iter()
+                .map(|(line, name, words)| match words.is_empty() {
+                    true => Err((*line, format!("inspector {name} needs components"))),
+                    false => Ok(InspectorDef {
+                        name: name.to_string(),
+                        components: words
+                            .iter()
+                            .map(|c| find(&components, "component", c, *line))
+                            .collect::<std::result::Result<_, _>>()?,
+                    }),
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            traced: false,
+        };
+
+        // every component is inspected by exactly one inspector and is
+        // used by a product, and every product has a workstation
+        for (c, (line, name, _)) in components.iter().enumerate() {
+            let inspected_by = topology
+                .inspectors
+                .iter()
+                .filter(|i| i.components.contains(&c))
+                .count();
+            match inspected_by {
+                0 => return Err((*line, format!("component {name} has no inspector"))),
+                1 => (),
+                _ => {
+                    return Err((
+                        *line,
+                        format!("component {name} has more than one inspector"),
+                    ))
+                }
+            }
+            if !topology.products.iter().any(|p| p.recipe.contains(&c)) {
+                return Err((
+                    *line,
+                    format!("component {name} is not used by any product"),
+                ));
+            }
+        }
+        for (p, (line, name, _)) in products.iter().enumerate() {
+            if !topology.workstations.iter().any(|ws| ws.product == p) {
+                return Err((*line, format!("product {name} has no workstation")));
+            }
+        }
+        // a stream's name on the command line must pick out one service
+        let services = topology.services();
+        for (i, service) in services.iter().enumerate() {
+            if services[..i].iter().any(|s| s.stream == service.stream) {
+                return Err((
+                    0,
+                    format!("two services would both be given by --{}", service.stream),
+                ));
+            }
+        }
+        Ok(topology)
+    }
+}
+
+#[derive(Debug)]
+pub enum FacilityError {
+    Io(PathBuf, io::Error),
+    // the line, and what was wrong with it
+    Format(PathBuf, usize, String),
+    // a problem with the facility as a whole
+    Invalid(PathBuf, String),
+}
+
+impl Display for FacilityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            Self::Format(path, line, message) => {
+                write!(f, "{}:{}: {}", path.display(), line, message)
+            }
+            Self::Invalid(path, message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for FacilityError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIKES: &str = "\
+# a facility with a product taking two wheels
+component Frame triangular(1, 4, 9)
+component Wheel exponential(3)
+product Bike Frame Wheel Wheel
+workstation Line1 Bike exponential(8)
+inspector Alice Frame Wheel
+";
+
+    // the line and message of the error parsing a facility
+    fn error(text: &str) -> (usize, String) {
+        Topology::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn parses_a_facility() {
+        let topology = Topology::parse(BIKES).unwrap();
+        assert_eq!(topology.components.len(), 2);
+        assert_eq!(topology.products[0].recipe, vec![0, 1, 1]);
+        assert_eq!(topology.workstations[0].product, 0);
+        assert_eq!(topology.inspectors[0].components, vec![0, 1]);
+        assert_eq!(topology.workstation(0).to_string(), "Line1");
+        assert!(!topology.traced);
+        // a component taken twice gets one buffer with room for both
+        let needed = topology
+            .buffers()
+            .iter()
+            .map(|b| b.needed)
+            .collect::<Vec<_>>();
+        assert_eq!(needed, vec![1, 2]);
+        assert_eq!(
+            topology.buffer_names(),
+            vec!["Frame of Line1", "Wheel of Line1"]
+        );
+        let streams = topology
+            .services()
+            .iter()
+            .map(|s| s.stream.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(streams, vec!["line1", "frame", "wheel"]);
+    }
+
+    #[test]
+    fn definitions_may_come_in_any_order() {
+        let reversed = BIKES.lines().rev().collect::<Vec<&str>>().join("\n");
+        let topology = Topology::parse(&reversed).unwrap();
+        let recipe = topology.products[0]
+            .recipe
+            .iter()
+            .map(|c| topology.components[*c].name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(recipe, vec!["Frame", "Wheel", "Wheel"]);
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        assert_eq!(error(&format!("{BIKES}machine M")).0, 7);
+        assert_eq!(
+            error(&format!("{BIKES}component Frame exponential(1)")),
+            (7, "component Frame is defined twice".to_string())
+        );
+        assert_eq!(
+            error(&BIKES.replace("Bike Frame Wheel Wheel", "Bike Frame Spoke")),
+            (4, "no component \"Spoke\"".to_string())
+        );
+        assert_eq!(
+            error(&BIKES.replace("exponential(8)", "exponential(-8)")).0,
+            5
+        );
+    }
+
+    #[test]
+    fn rejects_an_incomplete_facility() {
+        assert_eq!(
+            error(&BIKES.replace("Alice Frame Wheel", "Alice Frame")),
+            (3, "component Wheel has no inspector".to_string())
+        );
+        assert_eq!(
+            error(&format!("{BIKES}inspector Bob Wheel")),
+            (3, "component Wheel has more than one inspector".to_string())
+        );
+        assert_eq!(
+            error(&format!("{BIKES}product Trike Frame")),
+            (7, "product Trike has no workstation".to_string())
+        );
+        assert_eq!(
+            error("component Frame exponential(1)").1,
+            "the facility has no product"
+        );
+        // the stream of a workstation named like a component clashes
+        assert_eq!(
+            error(&format!("{BIKES}workstation frame Bike exponential(2)")).0,
+            0
+        );
+    }
+}
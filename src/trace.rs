@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter, Result};
+use std::fs;
+use std::io;
+use std::num::ParseFloatError;
+use std::path::{Path, PathBuf};
+
+use crate::simulation::Duration;
+
+// the observed service times shipped with the repo, in the
+// same order as the queues handed to the workstations and inspectors
+pub const DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/data");
+pub const ASSEMBLY_FILES: [&str; 3] = ["ws1.dat", "ws2.dat", "ws3.dat"];
+pub const INSPECTION_FILES: [&str; 3] = ["servinsp1.dat", "servinsp22.dat", "servinsp23.dat"];
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, usize, String, ParseFloatError),
+    // a value which parsed but cannot be a service time
+    Invalid(PathBuf, usize, f64),
+    Empty(PathBuf),
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            Self::Parse(path, line, content, e) => write!(
+                f,
+                "{}:{}: could not parse {:?} as a duration: {}",
+                path.display(),
+                line,
+                content,
+                e
+            ),
+            Self::Invalid(path, line, value) => write!(
+                f,
+                "{}:{}: {} is not a valid service time",
+                path.display(),
+                line,
+                value
+            ),
+            Self::Empty(path) => write!(f, "{} contains no service times", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+pub fn read_trace(path: &Path) -> std::result::Result<VecDeque<Duration>, TraceError> {
+    // reads one service time (in minutes) per line,
+    // skipping blank lines
+    let content = fs::read_to_string(path).map_err(|e| TraceError::Io(path.to_path_buf(), e))?;
+
+    let mut durations = VecDeque::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let duration = line
+            .parse::<Duration>()
+            .map_err(|e| TraceError::Parse(path.to_path_buf(), i + 1, line.to_string(), e))?;
+        let minutes = duration.as_minutes();
+        if !minutes.is_finite() || minutes < 0.0 {
+            return Err(TraceError::Invalid(path.to_path_buf(), i + 1, minutes));
+        }
+        durations.push_back(duration);
+    }
+
+    match durations.is_empty() {
+        true => Err(TraceError::Empty(path.to_path_buf())),
+        false => Ok(durations),
+    }
+}
+
+pub fn as_minutes(durations: &VecDeque<Duration>) -> Vec<f64> {
+    durations.iter().map(|d| d.as_minutes()).collect()
+}
+
+// the full set of observed service times for one facility
+#[derive(Clone, Debug)]
+pub struct Traces {
+    pub assembly: [VecDeque<Duration>; 3],
+    pub inspection: [VecDeque<Duration>; 3],
+}
+
+impl Traces {
+    pub fn load(dir: &Path) -> std::result::Result<Self, TraceError> {
+        let read = |name: &str| read_trace(&dir.join(name));
+        Ok(Traces {
+            assembly: [
+                read(ASSEMBLY_FILES[0])?,
+                read(ASSEMBLY_FILES[1])?,
+                read(ASSEMBLY_FILES[2])?,
+            ],
+            inspection: [
+                read(INSPECTION_FILES[0])?,
+                read(INSPECTION_FILES[1])?,
+                read(INSPECTION_FILES[2])?,
+            ],
+        })
+    }
+
+    // pairs each trace with the name of the file it was read from
+    pub fn named(&self) -> Vec<(&str, &VecDeque<Duration>)> {
+        ASSEMBLY_FILES
+            .iter()
+            .chain(INSPECTION_FILES.iter())
+            .copied()
+            .zip(self.assembly.iter().chain(self.inspection.iter()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // reads text written to a trace file of the test's own
+    fn read(test: &str, text: &str) -> std::result::Result<VecDeque<Duration>, TraceError> {
+        let path = std::env::temp_dir().join(format!("trace-{}-{test}.dat", std::process::id()));
+        fs::write(&path, text).unwrap();
+        let trace = read_trace(&path);
+        fs::remove_file(&path).unwrap();
+        trace
+    }
+
+    #[test]
+    fn reads_one_time_per_line() {
+        let trace = read("times", "1.5\n\n  2.25 \n0\n").unwrap();
+        assert_eq!(as_minutes(&trace), vec![1.5, 2.25, 0.0]);
+    }
+
+    #[test]
+    fn a_malformed_line_is_a_parse_error() {
+        match read("parse", "1.5\n\n2,5\n") {
+            Err(TraceError::Parse(_, line, content, _)) => {
+                assert_eq!((line, content.as_str()), (3, "2,5"))
+            }
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn a_negative_time_is_invalid() {
+        match read("negative", "1.5\n-0.5\n") {
+            Err(TraceError::Invalid(_, line, value)) => assert_eq!((line, value), (2, -0.5)),
+            other => panic!("expected an invalid time, got {other:?}"),
+        }
+        assert!(matches!(
+            read("infinite", "inf\n"),
+            Err(TraceError::Invalid(_, 1, _))
+        ));
+    }
+
+    #[test]
+    fn a_file_without_times_is_empty() {
+        assert!(matches!(read("empty", "\n  \n"), Err(TraceError::Empty(_))));
+        assert!(matches!(
+            read_trace(Path::new("no/such/file.dat")),
+            Err(TraceError::Io(..))
+        ));
+    }
+
+    #[test]
+    fn loads_the_observed_times() {
+        let traces = Traces::load(Path::new(DATA_DIR)).unwrap();
+        assert_eq!(traces.named().len(), 6);
+        assert!(traces.named().iter().all(|(_, trace)| !trace.is_empty()));
+    }
+}
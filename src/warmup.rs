@@ -0,0 +1,104 @@
+use crate::batch;
+
+// detection of the initial transient. the output of every replication
+// is cut into equal intervals of simulated time, giving one time series
+// per replication, and observations before the truncation point are
+// deleted from every statistic.
+
+pub fn completion_rate(times: &[f64], interval: f64, bins: usize) -> Vec<f64> {
+    // the number of completions per minute in each interval
+    batch::rates(times, &intervals(interval, bins))
+}
+
+pub fn time_average(steps: &[(f64, f64)], interval: f64, bins: usize) -> Vec<f64> {
+    // the time average of a step function in each interval
+    batch::step_averages(steps, &intervals(interval, bins))
+}
+
+fn intervals(interval: f64, bins: usize) -> Vec<f64> {
+    (0..=bins).map(|i| i as f64 * interval).collect()
+}
+
+pub fn welch(series: &[Vec<f64>], window: usize) -> Vec<f64> {
+    // averages the replications interval by interval, then smooths the
+    // average with a centred moving average of 2 * window + 1 intervals.
+    // near the start the window shrinks to fit, as Welch describes, and
+    // the last window intervals, which have no full window, are dropped
+    let length = series.iter().map(|s| s.len()).min().unwrap_or(0);
+    let average = (0..length)
+        .map(|i| series.iter().map(|s| s[i]).sum::<f64>() / series.len() as f64)
+        .collect::<Vec<f64>>();
+    (0..length.saturating_sub(window))
+        .map(|i| {
+            let w = i.min(window);
+            average[i - w..=i + w].iter().sum::<f64>() / (2 * w + 1) as f64
+        })
+        .collect()
+}
+
+pub fn mser(series: &[f64], batch: usize) -> usize {
+    // the number of observations MSER-b deletes: the truncation of
+    // the batch means which minimises their marginal standard error
+    //   sum over j >= d of (z_j - mean(z_d..))^2 / (k - d)^2
+    // searched over the first half only, where the statistic is stable
+    let means = series
+        .chunks_exact(batch)
+        .map(|chunk| chunk.iter().sum::<f64>() / batch as f64)
+        .collect::<Vec<f64>>();
+    let k = means.len();
+    (0..=k / 2)
+        .filter(|d| k - d > 1)
+        .map(|d| {
+            let rest = &means[d..];
+            let mean = rest.iter().sum::<f64>() / rest.len() as f64;
+            let squares = rest.iter().map(|z| (z - mean).powi(2)).sum::<f64>();
+            (d, squares / (rest.len() * rest.len()) as f64)
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map_or(0, |(d, _)| d * batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 100 observations stuck at 10 before settling to alternate
+    // about 0, so every batch of 5 after the transient is +-0.2
+    fn transient() -> Vec<f64> {
+        (0..1000)
+            .map(|i| match i < 100 {
+                true => 10.0,
+                false => [1.0, -1.0][i % 2],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mser_deletes_the_transient() {
+        assert_eq!(mser(&transient(), 5), 100);
+        assert_eq!(mser(&transient()[100..], 5), 0);
+    }
+
+    #[test]
+    fn welch_averages_then_smooths() {
+        // the replications average to 0, 6, 0, 6, 0, 6, and the
+        // window shrinks to fit at the start
+        let series = vec![
+            vec![0.0, 4.0, 0.0, 8.0, 0.0, 6.0],
+            vec![0.0, 8.0, 0.0, 4.0, 0.0, 6.0, 1.0],
+        ];
+        assert_eq!(welch(&series, 1), [0.0, 2.0, 4.0, 2.0, 4.0]);
+
+        // a moving average of the transient settles once
+        // the window has passed it
+        let smoothed = welch(&[transient()], 10);
+        assert_eq!(smoothed[89], 10.0);
+        assert!(smoothed[110..].iter().all(|x| x.abs() < 0.1));
+    }
+
+    #[test]
+    fn completion_rate_counts_each_interval() {
+        let times = [1.0, 2.0, 3.0, 12.0, 35.0];
+        assert_eq!(completion_rate(&times, 10.0, 3), [0.3, 0.1, 0.0]);
+    }
+}
@@ -1,140 +1,187 @@
 use std::collections::VecDeque;
 use std::fmt::{Display, Formatter, Result};
 
+use crate::checkpoint::{Checkpoint, CheckpointError, Reader, Writer};
 use crate::component::Component;
 use crate::event::EnqueueResult;
-use crate::event::FacilityEvent;
+use crate::event::{FacilityEvent, Topic};
 use crate::product::Product;
 use crate::simulation::Duration;
-use crate::simulation::SimulationActor;
+use crate::simulation::{Schedule, SimulationActor};
+use crate::topology::Kind;
 use crate::TimeStamp;
 
-type Buffer = [Option<Component>; 2];
+// the queue in front of a workstation for one kind of component. it
+// holds at most capacity components, and the workstation takes the
+// last to arrive first
+#[derive(Clone, Debug)]
+pub struct Buffer {
+    kind: Kind,
+    // the number of components each product takes from it
+    needed: usize,
+    capacity: usize,
+    // in the order they arrived
+    components: Vec<Component>,
+}
 
-impl Display for Type {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let print_buffer = |buf: &Buffer| {
-            if let Some(component) = buf[1] {
-                return format!("[{}, {}]", buf[0].unwrap(), component);
-            } else if let Some(component) = buf[0] {
-                return format!("[{}]", component);
+impl Buffer {
+    pub fn new(kind: Kind, needed: usize, capacity: usize) -> Self {
+        Buffer {
+            kind,
+            needed,
+            capacity,
+            components: vec![],
+        }
+    }
+
+    pub fn kind(&self) -> &Kind {
+        &self.kind
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.components.len() >= self.capacity
+    }
+
+    // whether it holds enough for the next product
+    pub fn has_enough(&self) -> bool {
+        self.components.len() >= self.needed
+    }
+
+    pub fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    pub fn push(&mut self, component: Component) -> bool {
+        // false, leaving the buffer as it was, if it is full
+        match self.is_full() {
+            true => false,
+            false => {
+                self.components.push(component);
+                true
             }
-            format!("[]")
-        };
-        match self {
-            Self::W1(buf) => write!(f, "{}", print_buffer(buf)),
-            Self::W2(buf1, buf2) => write!(f, "{} {}", print_buffer(buf1), print_buffer(buf2)),
-            Self::W3(buf1, buf2) => write!(f, "{} {}", print_buffer(buf1), print_buffer(buf2)),
         }
     }
+
+    fn take_last(&mut self) -> Option<Component> {
+        self.components.pop()
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum Type {
-    W1(Buffer),         // P1
-    W2(Buffer, Buffer), // P2
-    W3(Buffer, Buffer), // P3
+impl Display for Buffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let components = self
+            .components
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "[{}]", components.join(", "))
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let buffers = self
+            .buffers
+            .iter()
+            .map(|buf| buf.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}", buffers.join(" "))
+    }
+}
+
+// a workstation and its buffers, one for each kind of
+// component its product takes, in the order of the recipe
+#[derive(Clone, Debug)]
+pub struct Type {
+    kind: Kind,
+    buffers: Vec<Buffer>,
 }
 
 impl PartialEq<Type> for Type {
     fn eq(&self, other: &Type) -> bool {
-        std::mem::discriminant(self) == std::mem::discriminant(other)
+        self.kind == other.kind
     }
 }
 
 impl Type {
+    pub fn new(kind: Kind, buffers: Vec<Buffer>) -> Self {
+        Type { kind, buffers }
+    }
+
+    pub fn buffers(&self) -> &[Buffer] {
+        &self.buffers
+    }
+
     pub fn can_work(&self) -> bool {
-        match self {
-            Self::W1(buf) => matches!(buf[0], Some(_)),
-            Self::W2(buf1, buf2) => matches!(buf1[0], Some(_)) && matches!(buf2[0], Some(_)),
-            Self::W3(buf1, buf2) => matches!(buf1[0], Some(_)) && matches!(buf2[0], Some(_)),
-        }
+        self.buffers.iter().all(|buf| buf.has_enough())
     }
 
     pub fn contains(&self, component: Component) -> bool {
-        let in_buf = |buf: &Buffer, component: Component| -> bool {
-            buf[0] == Some(component) || buf[1] == Some(component)
-        };
-        match self {
-            Self::W1(buf) => in_buf(buf, component),
-            Self::W2(buf1, buf2) => in_buf(buf1, component) || in_buf(buf2, component),
-            Self::W3(buf1, buf2) => in_buf(buf1, component) || in_buf(buf2, component),
-        }
+        self.buffers
+            .iter()
+            .any(|buf| buf.components().contains(&component))
     }
 
-    fn present_count(buf: &Buffer) -> usize {
-        let count_slot = |i| match buf[i] {
-            Some(_) => 1,
-            None => 0,
-        };
-        count_slot(0) + count_slot(1)
+    // the buffer a component of kind waits in
+    pub fn buffer(&self, kind: &Kind) -> &Buffer {
+        self.buffers
+            .iter()
+            .find(|buf| buf.kind == *kind)
+            .unwrap_or_else(|| panic!("{} does not use {kind}", self.kind))
     }
 
-    pub fn c1_in_waiting(&self) -> usize {
-        match self {
-            Self::W1(buf) => Self::present_count(&buf),
-            Self::W2(buf1, _) => Self::present_count(&buf1),
-            Self::W3(buf1, _) => Self::present_count(&buf1),
-        }
+    fn buffer_mut(&mut self, kind: &Kind) -> &mut Buffer {
+        let name = &self.kind;
+        self.buffers
+            .iter_mut()
+            .find(|buf| buf.kind == *kind)
+            .unwrap_or_else(|| panic!("{name} does not use {kind}"))
     }
 
-    pub fn matching_count(&self, component: Component) -> usize {
-        match component {
-            Component::C1(..) => self.c1_in_waiting(),
-            _ => match self {
-                Self::W1(_) => panic!("{self} does not make {component}"),
-                Self::W2(_, buf2) => Self::present_count(buf2),
-                Self::W3(_, buf2) => Self::present_count(buf2),
-            },
-        }
+    // the number of components in every buffer
+    pub fn occupancy(&self) -> usize {
+        self.buffers.iter().map(|buf| buf.len()).sum()
     }
-    pub fn first_enqueue_time(&self) -> Option<TimeStamp> {
-        let c_min = |cf: Option<Component>, cs: Option<Component>| -> Option<Component> {
-            if cf.is_none() && cs.is_none() {
-                return None;
-            } else if cf.is_none() {
-                return cs;
-            } else if cs.is_none() {
-                return cf;
-            }
-            match matches!(
-                cf.unwrap()
-                    .inspection_start_time()
-                    .partial_cmp(&cs.unwrap().inspection_start_time()),
-                Some(std::cmp::Ordering::Greater)
-            ) {
-                true => cs,
-                false => cf,
-            }
-        };
 
-        let buf_min =
-            |buf1: Buffer, buf2: Buffer| c_min(c_min(buf1[0], buf1[1]), c_min(buf2[0], buf2[1]));
+    // the number of components of kind waiting
+    pub fn waiting(&self, kind: &Kind) -> usize {
+        self.buffer(kind).len()
+    }
 
-        let c = match self {
-            Self::W1(buf1) => c_min(buf1[0], buf1[1]),
-            Self::W2(buf1, buf2) => buf_min(*buf1, *buf2),
-            Self::W3(buf1, buf2) => buf_min(*buf1, *buf2),
-        };
+    pub fn is_full(&self, kind: &Kind) -> bool {
+        self.buffer(kind).is_full()
+    }
 
-        match c {
-            Some(c) => Some(c.inspection_start_time()),
-            None => None,
-        }
+    pub fn first_enqueue_time(&self) -> Option<TimeStamp> {
+        // the earliest inspection start of the components waiting
+        self.buffers
+            .iter()
+            .flat_map(|buf| buf.components())
+            .map(|c| c.inspection_start_time())
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
     }
 
-    pub fn name(&self) -> String {
-        let id = match self {
-            Self::W1(_) => "1",
-            Self::W2(..) => "2",
-            Self::W3(..) => "3",
-        };
-        format!("WS{id}")
+    pub fn name(&self) -> &str {
+        &self.kind.name
+    }
+
+    // its place among the workstations of the topology
+    pub fn index(&self) -> usize {
+        self.kind.index
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Workstation {
+    product: Kind,
     assembly_durations: VecDeque<Duration>,
     current_duration: Option<(TimeStamp, Duration)>, // (start time, duration)
     ws_type: Type,
@@ -159,26 +206,41 @@ impl Display for Workstation {
 }
 
 impl Workstation {
-    pub fn new(ws_type: Type, assembly_durations: VecDeque<Duration>) -> Workstation {
+    pub fn new(
+        ws_type: Type,
+        product: Kind,
+        assembly_durations: VecDeque<Duration>,
+    ) -> Workstation {
         Workstation {
+            product,
             assembly_durations,
-            ws_type,
             current_duration: None,
             products: vec![],
-            buffer_states: vec![(TimeStamp::start(), ws_type)]
+            buffer_states: vec![(TimeStamp::start(), ws_type.clone())],
+            ws_type,
         }
     }
 
-    pub fn name(&self) -> String {
+    pub fn name(&self) -> &str {
         self.ws_type.name()
     }
 
+    pub fn index(&self) -> usize {
+        self.ws_type.index()
+    }
+
     pub fn is_working(&self) -> bool {
-        matches!(self.current_duration, Some(_))
+        self.current_duration.is_some()
     }
 
-    pub fn matching_count(&self, component: Component) -> usize {
-        self.ws_type.matching_count(component)
+    // the time it finishes the product it is working on, if any
+    fn next_event_time(&self) -> Option<TimeStamp> {
+        self.current_duration
+            .map(|(start_time, duration)| start_time + duration)
+    }
+
+    pub fn is_full(&self, kind: &Kind) -> bool {
+        self.ws_type.is_full(kind)
     }
 
     fn assemble(&mut self, timestamp: TimeStamp) -> Product {
@@ -188,75 +250,32 @@ impl Workstation {
             "assemble called but WS could not work"
         );
 
-        let take_first_avail = |buf: &mut Buffer| -> Component {
-            match buf[1] {
-                Some(c) => {
-                    let comp = c;
-                    buf[1] = None;
-                    comp
-                }
-                None => {
-                    let comp = buf[0].unwrap();
-                    buf[0] = None;
-                    comp
-                }
-            }
-        };
-
-        match &mut self.ws_type {
-            Type::W1(buf) => {
-                let component = take_first_avail(buf);
-                Product::from(component, None, timestamp)
-            }
-            Type::W2(buf1, buf2) => {
-                let first = take_first_avail(buf1);
-                let second = take_first_avail(buf2);
-                Product::from(first, Some(second), timestamp)
-            }
-            Type::W3(buf1, buf2) => {
-                let first = take_first_avail(buf1);
-                let second = take_first_avail(buf2);
-                Product::from(first, Some(second), timestamp)
+        // the workstation can work, so every buffer holds enough
+        let mut components = vec![];
+        for buf in self.ws_type.buffers.iter_mut() {
+            for _ in 0..buf.needed {
+                components.push(buf.take_last().unwrap());
             }
         }
+        Product::new(self.product.clone(), components, timestamp)
     }
 
-    pub fn c1_in_waiting(&self) -> usize {
-        self.ws_type.c1_in_waiting()
+    // the number of components of kind waiting
+    pub fn waiting(&self, kind: &Kind) -> usize {
+        self.ws_type.waiting(kind)
     }
 
-    pub fn enqueue(&mut self, ins1: bool, c: Component, now: TimeStamp) -> EnqueueResult {
-        assert!(c.is_finished(), "{} {}", ins1, c);
+    pub fn enqueue(&mut self, c: Component, now: TimeStamp) -> EnqueueResult {
+        assert!(c.is_finished(), "{}", c);
 
-        let add_to_buffer = |buf: &mut Buffer, mut c: Component| {
-            // put c in next available slot in buffer
-            // buffer cannot be full
-            c.set_enqueued(now);
-            if !buf[1].is_none() {
-                return (false, *buf);
-            }
-            match buf[0] {
-                Some(_) => buf[1] = Some(c),
-                None => buf[0] = Some(c),
-            };
-            (true, *buf)
-        };
-
-        let decide_buffer = |buf_c1: &mut Buffer, other_buffer: &mut Buffer, c| match c {
-            Component::C1(..) => add_to_buffer(buf_c1, c),
-            Component::C2(..) => add_to_buffer(other_buffer, c),
-            Component::C3(..) => add_to_buffer(other_buffer, c),
-        };
-
-        let (result, _) = match &mut self.ws_type {
-            Type::W1(buf_c1) => add_to_buffer(buf_c1, c),
-            Type::W2(buf_c1, buf_c2) => decide_buffer(buf_c1, buf_c2, c),
-            Type::W3(buf_c1, buf_c3) => decide_buffer(buf_c1, buf_c3, c),
-        };
-        match result {
+        // put c in next available slot in its buffer
+        // unless it is full
+        let mut queued = c.clone();
+        queued.set_enqueued(now);
+        match self.ws_type.buffer_mut(&c.kind).push(queued) {
             true => {
-                self.buffer_states.push((now, self.ws_type));
-                EnqueueResult::CouldEnqueue(ins1, c, self.ws_type, now, self.is_working())
+                self.buffer_states.push((now, self.ws_type.clone()));
+                EnqueueResult::CouldEnqueue(c, self.ws_type.clone(), now, self.is_working())
             }
             false => EnqueueResult::Fail,
         }
@@ -264,28 +283,36 @@ impl Workstation {
 
     fn start(&mut self, start_time: TimeStamp) {
         assert!(
-            matches!(self.current_duration, None),
+            self.current_duration.is_none(),
             "WS {} which is already working was started",
             self.name()
         );
-        let duration = self.assembly_durations.pop_front().expect(
-            format!(
+        let duration = self.assembly_durations.pop_front().unwrap_or_else(|| {
+            panic!(
                 "WS was started but \
             has no remaining duration {}",
                 self.products.len()
             )
-            .as_str(),
-        );
+        });
         self.current_duration = Some((start_time, duration));
     }
 }
 
 impl SimulationActor for Workstation {
-    fn respond_to(&mut self, event: FacilityEvent) -> Option<FacilityEvent> {
+    fn topics(&self) -> Vec<Topic> {
+        vec![Topic::WorkstationStarted(self.ws_type.index())]
+    }
+
+    fn respond_to(
+        &mut self,
+        event: FacilityEvent,
+        schedule: &mut Schedule,
+    ) -> Option<FacilityEvent> {
         match event {
             FacilityEvent::WorkstationStarted(ws, start_time) => {
-                if &self.ws_type == &ws {
+                if self.ws_type == ws {
                     self.start(start_time);
+                    schedule.set(self.next_event_time());
                 }
                 None
             }
@@ -293,37 +320,171 @@ impl SimulationActor for Workstation {
         }
     }
 
-    fn respond(&mut self, now: TimeStamp) -> Option<FacilityEvent> {
+    fn respond(&mut self, now: TimeStamp, schedule: &mut Schedule) -> Option<FacilityEvent> {
         // time until done should be zero, given margin of error for f64
-        let time_until_done = self.duration_until_next_event(now).expect(
-            format!(
-                "WS {} called with respond(now, duration) but isn't marked \
+        let time_until_done = self
+            .next_event_time()
+            .map(|ts| ts - now)
+            .unwrap_or_else(|| {
+                panic!(
+                    "WS {} called with respond(now, duration) but isn't marked \
                 as working (has no self.current_duration)",
-                self.name().as_str()
-            )
-            .as_str(),
-        );
+                    self.name()
+                )
+            });
         assert!(time_until_done.as_minutes() <= 1000.0 * f64::EPSILON);
 
         self.current_duration = None;
         let product = self.assemble(now);
-        let assembly_event = FacilityEvent::Assembled(product, self.ws_type);
+        let assembly_event = FacilityEvent::Assembled(product.clone(), self.ws_type.clone());
 
         self.products.push(product);
-        self.buffer_states.push((now, self.ws_type));
+        self.buffer_states.push((now, self.ws_type.clone()));
 
         // start working on the next product if it can
         if self.ws_type.can_work() {
             self.start(now);
         }
+        schedule.set(self.next_event_time());
 
         Some(assembly_event)
     }
+}
 
-    fn duration_until_next_event(&self, now: TimeStamp) -> Option<Duration> {
+impl Checkpoint for Workstation {
+    fn save(&self, out: &mut Writer) {
+        out.line("workstation").word(self.name());
+        out.line("durations").durations(&self.assembly_durations);
+        out.line("current");
         match self.current_duration {
-            Some((start_time, duration)) => Some(start_time + duration - now),
+            Some((start_time, duration)) => out.time(Some(start_time)).value(duration.as_minutes()),
+            None => out.word("-"),
+        };
+        out.line("buffers").ws_type(&self.ws_type);
+        out.line("products").value(self.products.len());
+        for product in self.products.iter() {
+            out.line("product").product(product);
+        }
+        out.line("buffer_states").value(self.buffer_states.len());
+        for (ts, ws_type) in self.buffer_states.iter() {
+            out.line("state").time(Some(*ts)).ws_type(ws_type);
+        }
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> std::result::Result<(), CheckpointError> {
+        input.expect("workstation")?;
+        input.expect(self.name())?;
+        input.expect("durations")?;
+        let assembly_durations = input.durations()?;
+        input.expect("current")?;
+        let current_duration = match input.time()? {
+            Some(start_time) => Some((start_time, Duration::of_minutes(input.value()?))),
             None => None,
+        };
+        input.expect("buffers")?;
+        let ws_type = input.ws_type()?;
+        if ws_type != self.ws_type {
+            return Err(input.error(format!(
+                "{} cannot hold the buffers of {}",
+                self.name(),
+                ws_type.name()
+            )));
+        }
+        input.expect("products")?;
+        let products = (0..input.value::<usize>()?)
+            .map(|_| {
+                input.expect("product")?;
+                input.product()
+            })
+            .collect::<std::result::Result<Vec<Product>, _>>()?;
+        input.expect("buffer_states")?;
+        let buffer_states = (0..input.value::<usize>()?)
+            .map(|_| {
+                input.expect("state")?;
+                let ts = input
+                    .time()?
+                    .ok_or_else(|| input.error("a buffer state needs a time".to_string()))?;
+                Ok((ts, input.ws_type()?))
+            })
+            .collect::<std::result::Result<Vec<(TimeStamp, Type)>, CheckpointError>>()?;
+
+        *self = Workstation {
+            product: self.product.clone(),
+            assembly_durations,
+            current_duration,
+            ws_type,
+            products,
+            buffer_states,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(index: usize, name: &str) -> Kind {
+        Kind {
+            index,
+            name: name.into(),
         }
     }
+
+    // a wheel which took minutes to inspect, to tell them apart
+    fn wheel(minutes: f64) -> Component {
+        Component::new(Duration::of_minutes(minutes), kind(1, "Wheel"))
+    }
+
+    fn filled(capacity: usize, count: usize) -> (Buffer, Vec<bool>) {
+        let mut buffer = Buffer::new(kind(1, "Wheel"), 2, capacity);
+        let pushed = (0..count).map(|i| buffer.push(wheel(i as f64))).collect();
+        (buffer, pushed)
+    }
+
+    #[test]
+    fn a_buffer_of_no_capacity_takes_nothing() {
+        let (buffer, pushed) = filled(0, 1);
+        assert_eq!(pushed, [false]);
+        assert!(buffer.is_full() && buffer.len() == 0 && !buffer.has_enough());
+    }
+
+    #[test]
+    fn a_full_buffer_is_left_as_it_was() {
+        let (buffer, pushed) = filled(1, 2);
+        assert_eq!(pushed, [true, false]);
+        assert!(buffer.is_full() && !buffer.has_enough());
+        assert_eq!(buffer.components()[0].duration.as_minutes(), 0.0);
+    }
+
+    #[test]
+    fn a_large_buffer_fills_up_to_its_capacity() {
+        let (buffer, pushed) = filled(1000, 1001);
+        assert!(pushed[..1000].iter().all(|p| *p) && !pushed[1000]);
+        assert_eq!(buffer.len(), 1000);
+        assert!(buffer.is_full() && buffer.has_enough());
+    }
+
+    #[test]
+    fn the_last_to_arrive_is_taken_first() {
+        let (mut buffer, _) = filled(3, 3);
+        let taken = (0..3)
+            .map(|_| buffer.take_last().unwrap().duration.as_minutes())
+            .collect::<Vec<f64>>();
+        assert_eq!(taken, [2.0, 1.0, 0.0]);
+        assert!(buffer.take_last().is_none());
+    }
+
+    #[test]
+    fn a_workstation_works_once_every_buffer_has_enough() {
+        let (wheels, _) = filled(2, 2);
+        let mut frames = Buffer::new(kind(0, "Frame"), 1, 2);
+        let mut line = Type::new(kind(0, "Line1"), vec![frames.clone(), wheels.clone()]);
+        assert!(!line.can_work());
+        frames.push(Component::new(Duration::of_minutes(1.0), kind(0, "Frame")));
+        line = Type::new(kind(0, "Line1"), vec![frames, wheels]);
+        assert!(line.can_work());
+        assert_eq!(line.occupancy(), 3);
+        assert!(line.is_full(&kind(1, "Wheel")) && !line.is_full(&kind(0, "Frame")));
+    }
 }